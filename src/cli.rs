@@ -0,0 +1,311 @@
+use clap::{Args, Parser, Subcommand};
+
+use std::path::PathBuf;
+
+use novel_packer::core::options::{ChapterSelection, DownloadOptions, VolumeSelection};
+use novel_packer::utils::fetch::record::FetchMode;
+
+/// 轻小说打包器，不带参数运行时进入交互模式
+#[derive(Debug, Parser)]
+#[command(name = "novel-packer", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// 把访问的页面、single-file的输出和图片保存到目录，用于复现问题
+    #[arg(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// 只从录制的目录读取页面和图片，不访问网络也不启动浏览器
+    #[arg(long, global = true, value_name = "DIR")]
+    pub replay: Option<PathBuf>,
+}
+
+impl Cli {
+    /// 下载后端的运行模式
+    pub fn fetch_mode(&self) -> FetchMode {
+        match (&self.record, &self.replay) {
+            (Some(dir), _) => FetchMode::Record(dir.clone()),
+            (None, Some(dir)) => FetchMode::Replay(dir.clone()),
+            (None, None) => FetchMode::Live,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 显示书籍信息和卷列表
+    Info {
+        /// 书籍链接
+        url: String,
+    },
+    /// 显示指定卷的章节列表
+    Chapters {
+        /// 书籍链接
+        url: String,
+        #[command(flatten)]
+        volumes: VolumeArgs,
+        #[command(flatten)]
+        chapters: ChapterArgs,
+    },
+    /// 下载、检查并打包指定的卷
+    Download {
+        /// 书籍链接
+        url: String,
+        #[command(flatten)]
+        volumes: VolumeArgs,
+        #[command(flatten)]
+        chapters: ChapterArgs,
+        #[command(flatten)]
+        series: SeriesArgs,
+        /// 发现未完成的下载时重新开始，默认恢复下载
+        #[arg(long)]
+        restart: bool,
+        #[command(flatten)]
+        steps: StepArgs,
+    },
+    /// 检查已下载的卷，重新下载缺页和缺少的图片
+    Check {
+        /// 书籍链接
+        url: String,
+        #[command(flatten)]
+        volumes: VolumeArgs,
+        #[command(flatten)]
+        chapters: ChapterArgs,
+    },
+    /// 将已下载的卷打包为epub
+    Pack {
+        /// 书籍链接
+        url: String,
+        #[command(flatten)]
+        volumes: VolumeArgs,
+        #[command(flatten)]
+        chapters: ChapterArgs,
+        #[command(flatten)]
+        series: SeriesArgs,
+        /// 打包成功后删除下载数据
+        #[arg(long)]
+        clean: bool,
+        /// 将所有卷打包为一本合集
+        #[arg(long)]
+        omnibus: bool,
+    },
+    /// 恢复未完成的下载
+    Resume {
+        /// 书籍链接
+        url: String,
+        #[command(flatten)]
+        volumes: VolumeArgs,
+        #[command(flatten)]
+        chapters: ChapterArgs,
+        #[command(flatten)]
+        series: SeriesArgs,
+        #[command(flatten)]
+        steps: StepArgs,
+    },
+    /// 删除指定卷的下载数据
+    Clean {
+        /// 书籍链接
+        url: String,
+        #[command(flatten)]
+        volumes: VolumeArgs,
+        #[command(flatten)]
+        chapters: ChapterArgs,
+    },
+    /// 列出可以恢复的下载
+    ListStates,
+    /// 校验epub文件，发现错误时返回非零退出码
+    Validate {
+        /// 要校验的epub文件
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// 从文件读取链接批量下载，每行一个链接，链接后可以跟要下载的卷
+    Batch {
+        /// 链接列表文件
+        file: PathBuf,
+        /// 发现未完成的下载时重新开始
+        #[arg(long)]
+        restart: bool,
+        #[command(flatten)]
+        steps: StepArgs,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct VolumeArgs {
+    /// 要处理的卷，支持逗号分隔和 a-b 范围格式，如 1-3,5；-1 为最后一卷；
+    /// all、last、new 分别表示所有卷、最后一卷和还没有打包的卷；/正则/ 或其他文字按卷名选择
+    #[arg(short, long, required = true, allow_hyphen_values = true, value_parser = parse_volumes)]
+    pub volumes: Vec<VolumeSelection>,
+}
+
+#[derive(Debug, Args)]
+pub struct ChapterArgs {
+    /// 每一卷中要处理的章节，支持逗号分隔和 a-b 范围格式，如 3-7,12，序号见 chapters 命令；
+    /// 只处理部分章节时下载数据和整卷分开保存，检查、打包和删除时需要指定相同的章节
+    #[arg(short, long, value_parser = parse_chapters)]
+    pub chapters: Option<ChapterSelection>,
+    /// 跳过插图章节
+    #[arg(long)]
+    pub skip_illustrations: bool,
+    /// 跳过后记
+    #[arg(long)]
+    pub skip_afterword: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SeriesArgs {
+    /// epub中的系列名，默认为书名
+    #[arg(long)]
+    pub series: Option<String>,
+    /// 在系列中的序号，选择多卷时为第一卷的序号，之后的卷依次加一，默认为卷的序号加一
+    #[arg(long)]
+    pub series_index: Option<u32>,
+}
+
+#[derive(Debug, Args)]
+pub struct StepArgs {
+    /// 下载完成后不检查章节
+    #[arg(long)]
+    pub no_check: bool,
+    /// 不打包epub
+    #[arg(long)]
+    pub no_pack: bool,
+    /// 打包成功后删除下载数据
+    #[arg(long)]
+    pub clean: bool,
+    /// 将所有卷打包为一本合集，目录分为卷和章节两级
+    #[arg(long, conflicts_with = "no_pack")]
+    pub omnibus: bool,
+}
+
+fn parse_volumes(input: &str) -> Result<VolumeSelection, String> {
+    VolumeSelection::parse(input).map_err(|e| e.to_string())
+}
+
+fn parse_chapters(input: &str) -> Result<ChapterSelection, String> {
+    ChapterSelection::parse(input).map_err(|e| e.to_string())
+}
+
+impl VolumeArgs {
+    fn options(&self) -> DownloadOptions {
+        let selection = self
+            .volumes
+            .iter()
+            .cloned()
+            .reduce(VolumeSelection::union)
+            .unwrap_or(VolumeSelection::All);
+        DownloadOptions::default().with_volumes(selection)
+    }
+}
+
+impl ChapterArgs {
+    fn apply(&self, options: DownloadOptions) -> DownloadOptions {
+        let selection = ChapterSelection {
+            skip_illustrations: self.skip_illustrations,
+            skip_afterword: self.skip_afterword,
+            ..self.chapters.clone().unwrap_or_default()
+        };
+        options.with_chapters(selection)
+    }
+}
+
+impl SeriesArgs {
+    fn apply(&self, mut options: DownloadOptions) -> DownloadOptions {
+        options.series = self.series.clone();
+        options.series_index = self.series_index;
+        options
+    }
+}
+
+impl StepArgs {
+    fn apply(&self, mut options: DownloadOptions) -> DownloadOptions {
+        options.check = Some(!self.no_check);
+        options.pack = Some(!self.no_pack);
+        options.clean = Some(self.clean);
+        options.omnibus = self.omnibus;
+        options
+    }
+}
+
+impl Command {
+    /// 命令对应的书籍链接，批量下载没有单独的链接
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Command::Info { url }
+            | Command::Chapters { url, .. }
+            | Command::Download { url, .. }
+            | Command::Check { url, .. }
+            | Command::Pack { url, .. }
+            | Command::Resume { url, .. }
+            | Command::Clean { url, .. } => Some(url),
+            Command::Batch { .. } | Command::ListStates | Command::Validate { .. } => None,
+        }
+    }
+
+    /// 将命令行参数转换为下载选项，命令行模式下不会询问任何问题
+    pub fn options(&self) -> DownloadOptions {
+        match self {
+            Command::Info { .. } | Command::ListStates | Command::Validate { .. } => {
+                DownloadOptions::default()
+            }
+            Command::Chapters {
+                volumes, chapters, ..
+            } => chapters.apply(volumes.options()),
+            Command::Download {
+                volumes,
+                chapters,
+                series,
+                restart,
+                steps,
+                ..
+            } => {
+                let mut options = steps.apply(series.apply(chapters.apply(volumes.options())));
+                options.resume = Some(!*restart);
+                options
+            }
+            Command::Check {
+                volumes, chapters, ..
+            } => DownloadOptions {
+                download: false,
+                check: Some(true),
+                pack: Some(false),
+                clean: Some(false),
+                ..chapters.apply(volumes.options())
+            },
+            Command::Pack {
+                volumes,
+                chapters,
+                series,
+                clean,
+                omnibus,
+                ..
+            } => DownloadOptions {
+                download: false,
+                check: Some(false),
+                pack: Some(true),
+                clean: Some(*clean),
+                omnibus: *omnibus,
+                ..series.apply(chapters.apply(volumes.options()))
+            },
+            Command::Resume {
+                volumes,
+                chapters,
+                series,
+                steps,
+                ..
+            } => {
+                let mut options = steps.apply(series.apply(chapters.apply(volumes.options())));
+                options.resume = Some(true);
+                options
+            }
+            Command::Clean {
+                volumes, chapters, ..
+            } => chapters.apply(volumes.options()),
+            Command::Batch { restart, steps, .. } => {
+                let mut options = steps.apply(DownloadOptions::default());
+                options.resume = Some(!*restart);
+                options
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use colored::Colorize;
+use tracing::{error, info};
+
+use crate::core::get_struct::get_struct_by_url;
+use crate::core::options::{DownloadOptions, VolumeSelection};
+use crate::core::report::BookReport;
+use crate::utils::input::interrupted;
+
+/// 链接列表中的一项
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchEntry {
+    pub url: String,
+    /// 没有指定时下载所有卷
+    pub volumes: VolumeSelection,
+}
+
+/// 解析链接列表，每行一个链接，链接后可以跟要下载的卷；空行和以 # 开头的行会被忽略
+///
+/// ```text
+/// https://www.linovelib.com/novel/2356.html 1-3,5
+/// https://www.linovelib.com/novel/3095.html
+/// ```
+pub fn parse_batch_list(content: &str) -> Result<Vec<BatchEntry>> {
+    let mut entries = Vec::new();
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (url, selection) = match line.split_once(char::is_whitespace) {
+            Some((url, selection)) => (url, selection.trim()),
+            None => (line, ""),
+        };
+        let volumes = if selection.is_empty() {
+            VolumeSelection::All
+        } else {
+            VolumeSelection::parse(selection)
+                .map_err(|e| anyhow!("第{}行卷的格式错误：{}", line_no + 1, e))?
+        };
+        entries.push(BatchEntry {
+            url: url.to_string(),
+            volumes,
+        });
+    }
+    Ok(entries)
+}
+
+/// 从文件读取链接列表
+pub fn load_batch_file<P: AsRef<Path>>(path: P) -> Result<Vec<BatchEntry>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("无法读取链接列表: {:?}", path))?;
+    parse_batch_list(&content)
+}
+
+/// 依次下载列表中的每一本书，一本书失败不会影响其余的书
+pub async fn run_batch(entries: Vec<BatchEntry>, options: &DownloadOptions) -> Vec<BookReport> {
+    let mut reports = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.into_iter().enumerate() {
+        if interrupted() {
+            eprintln!("下载已中断，跳过剩下的书");
+            break;
+        }
+        println!(
+            "\n{}",
+            format!("[{}] {}", i + 1, entry.url).bright_yellow().bold()
+        );
+        let mut report = BookReport::new(&entry.url);
+        let options = options.clone().with_volumes(entry.volumes);
+        if let Err(e) = run_entry(&entry.url, &options, &mut report).await {
+            error!("{}处理失败：{}", entry.url, e);
+            eprintln!("{}处理失败：{}", entry.url, e);
+            report.error = Some(e.to_string());
+        } else {
+            info!("{}处理完成", entry.url);
+        }
+        reports.push(report);
+    }
+    reports
+}
+
+async fn run_entry(url: &str, options: &DownloadOptions, report: &mut BookReport) -> Result<()> {
+    let mut novel = get_struct_by_url(url)?;
+    novel.check()?;
+    novel.display().await?;
+    report.book_name = novel.book_name().to_string();
+    report.volumes = novel.download(options).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch_list() {
+        let content = "
+# 注释
+https://www.linovelib.com/novel/2356.html 1-3,5
+https://www.linovelib.com/novel/3095.html
+
+https://www.linovelib.com/novel/1.html   0 2
+";
+        let entries = parse_batch_list(content).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].url, "https://www.linovelib.com/novel/2356.html");
+        assert_eq!(entries[0].volumes, VolumeSelection::Indices(vec![1..=3, 5..=5]));
+        assert_eq!(entries[1].volumes, VolumeSelection::All);
+        assert_eq!(entries[2].volumes, VolumeSelection::Indices(vec![0..=0, 2..=2]));
+    }
+
+    #[test]
+    fn test_parse_batch_list_invalid_volumes() {
+        let content = "https://www.linovelib.com/novel/2356.html 3-1";
+        assert!(parse_batch_list(content).is_err());
+    }
+}
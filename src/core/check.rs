@@ -0,0 +1,120 @@
+use crate::core::model::{ImageRef, Volume};
+use crate::utils::httpserver::get_image_by_url;
+
+/// 有问题的章节
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterProblem {
+    /// 章节在卷中的序号
+    pub index: usize,
+    pub title: String,
+    pub url: String,
+    pub missing_pages: bool,
+    pub missing_images: Vec<ImageRef>,
+}
+
+/// 一卷的检查结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheckResult {
+    pub missing_cover: bool,
+    pub chapters: Vec<ChapterProblem>,
+}
+
+impl CheckResult {
+    pub fn is_ok(&self) -> bool {
+        !self.missing_cover && self.chapters.is_empty()
+    }
+
+    /// 缺页或缺少图片的章节标题
+    pub fn failed_chapters(&self) -> Vec<String> {
+        self.chapters.iter().map(|c| c.title.clone()).collect()
+    }
+
+    /// 缺页或缺少图片的章节序号
+    pub fn failed_indices(&self) -> Vec<usize> {
+        self.chapters.iter().map(|c| c.index).collect()
+    }
+}
+
+/// 检查一卷是否缺页或缺少图片，图片从全局图片存储中查找
+pub fn check_volume(volume: &Volume) -> CheckResult {
+    check_volume_with(volume, |image| {
+        matches!(get_image_by_url(&image.url), Ok(Some(_)))
+    })
+}
+
+/// 检查一卷是否缺页或缺少图片，`has_image` 判断图片是否已经下载
+pub fn check_volume_with<F>(volume: &Volume, has_image: F) -> CheckResult
+where
+    F: Fn(&ImageRef) -> bool,
+{
+    let missing_cover = volume.cover.as_ref().is_some_and(|c| !has_image(c));
+    let chapters = volume
+        .chapters
+        .iter()
+        .enumerate()
+        .filter_map(|(index, chapter)| {
+            let missing_pages = chapter.has_missing_pages();
+            let missing_images: Vec<ImageRef> = chapter
+                .images
+                .iter()
+                .filter(|image| !has_image(image))
+                .cloned()
+                .collect();
+            if missing_pages || !missing_images.is_empty() {
+                Some(ChapterProblem {
+                    index,
+                    title: chapter.title.clone(),
+                    url: chapter.url.clone(),
+                    missing_pages,
+                    missing_images,
+                })
+            } else {
+                None
+            }
+        })
+        .collect();
+    CheckResult {
+        missing_cover,
+        chapters,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::model::Chapter;
+
+    fn chapter(title: &str, pages: &[&str], images: &[&str]) -> Chapter {
+        Chapter {
+            pages: pages.iter().map(|p| p.to_string()).collect(),
+            images: images.iter().map(|i| ImageRef::new(i)).collect(),
+            ..Chapter::new(&format!("https://example.com/{}", title), title)
+        }
+    }
+
+    #[test]
+    fn test_check_volume_with() {
+        let mut volume = Volume::new("https://example.com/vol", "第一卷");
+        volume.cover = Some(ImageRef::new("cover.jpg"));
+        volume.chapters = vec![
+            chapter("完整", &["<p>a</p>"], &["1.jpg"]),
+            chapter("缺页", &["<p>a</p>", ""], &[]),
+            chapter("缺图", &["<p>a</p>"], &["1.jpg", "2.jpg"]),
+            chapter("没有内容", &[], &[]),
+        ];
+
+        let result = check_volume_with(&volume, |image| image.url != "2.jpg");
+        assert!(!result.is_ok());
+        assert!(!result.missing_cover);
+        assert_eq!(result.failed_chapters(), vec!["缺页", "缺图", "没有内容"]);
+        assert_eq!(result.failed_indices(), vec![1, 2, 3]);
+        assert_eq!(
+            result.chapters[1].missing_images,
+            vec![ImageRef::new("2.jpg")]
+        );
+        assert!(!result.chapters[1].missing_pages);
+
+        let result = check_volume_with(&volume, |_| false);
+        assert!(result.missing_cover);
+    }
+}
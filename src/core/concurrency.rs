@@ -0,0 +1,186 @@
+//! 自适应并发
+//!
+//! 章节下载遇到反爬页面或错误时并发数减半，连续成功后每次加一（AIMD），
+//! 这样长的卷不需要手动调整 `max_concurrent` 也能下载完。
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+/// 两次减半之间的最短间隔，同一批并发的请求一起失败时只减半一次
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(2);
+
+/// 并发数在 `1..=max` 之间调整的限制器
+pub struct AdaptiveConcurrency {
+    max: usize,
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+struct State {
+    /// 当前允许的并发数
+    limit: usize,
+    /// 正在进行的任务数
+    active: usize,
+    /// 上次调整后连续成功的次数
+    successes: usize,
+    /// 暂停时不再发放名额，正在进行的任务不受影响
+    paused: bool,
+    last_decrease: Option<Instant>,
+}
+
+/// 并发名额，释放时唤醒等待的任务
+pub struct Permit<'a> {
+    owner: &'a AdaptiveConcurrency,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.owner.release();
+    }
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            state: Mutex::new(State {
+                limit: max,
+                active: 0,
+                successes: 0,
+                paused: false,
+                last_decrease: None,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// 当前允许的并发数
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// 最大并发数
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// 暂停或继续发放名额
+    pub fn set_paused(&self, paused: bool) {
+        self.state.lock().unwrap().paused = paused;
+        if !paused {
+            self.notify.notify_waiters();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    /// 等待直到没有暂停，并且正在进行的任务数小于当前并发数
+    pub async fn acquire(&self) -> Permit<'_> {
+        loop {
+            // 先注册等待再检查，避免错过检查之后的唤醒
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            {
+                let mut state = self.state.lock().unwrap();
+                if !state.paused && state.active < state.limit {
+                    state.active += 1;
+                    return Permit { owner: self };
+                }
+            }
+            notified.await;
+        }
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().active -= 1;
+        self.notify.notify_waiters();
+    }
+
+    /// 记录一次成功，连续成功的次数达到当前并发数时并发数加一，返回调整后的并发数
+    pub fn on_success(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.successes += 1;
+        if state.successes >= state.limit && state.limit < self.max {
+            state.limit += 1;
+            state.successes = 0;
+            drop(state);
+            self.notify.notify_waiters();
+            return self.limit();
+        }
+        state.limit
+    }
+
+    /// 记录一次拦截或错误，并发数减半，返回调整后的并发数
+    pub fn on_failure(&self) -> usize {
+        self.on_failure_at(Instant::now())
+    }
+
+    fn on_failure_at(&self, now: Instant) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.successes = 0;
+        if state
+            .last_decrease
+            .is_some_and(|last| now.duration_since(last) < DECREASE_COOLDOWN)
+        {
+            return state.limit;
+        }
+        state.limit = (state.limit / 2).max(1);
+        state.last_decrease = Some(now);
+        state.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_adaptive_concurrency() {
+        let concurrency = AdaptiveConcurrency::new(8);
+        let start = Instant::now();
+        assert_eq!(concurrency.on_failure_at(start), 4);
+        // 冷却时间内的失败不再减半
+        assert_eq!(concurrency.on_failure_at(start + Duration::from_secs(1)), 4);
+        assert_eq!(concurrency.on_failure_at(start + Duration::from_secs(3)), 2);
+        assert_eq!(concurrency.on_failure_at(start + Duration::from_secs(6)), 1);
+        assert_eq!(concurrency.on_failure_at(start + Duration::from_secs(9)), 1);
+
+        // 只有一个名额时第二个任务要等第一个释放
+        let permit = concurrency.acquire().await;
+        let waiting = concurrency.acquire();
+        tokio::pin!(waiting);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), waiting.as_mut())
+                .await
+                .is_err()
+        );
+        drop(permit);
+        drop(waiting.await);
+
+        // 暂停时不发放名额，继续后等待的任务才能开始
+        concurrency.set_paused(true);
+        let waiting = concurrency.acquire();
+        tokio::pin!(waiting);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), waiting.as_mut())
+                .await
+                .is_err()
+        );
+        concurrency.set_paused(false);
+        drop(waiting.await);
+
+        assert_eq!(concurrency.on_success(), 2);
+        assert_eq!(concurrency.on_success(), 2);
+        assert_eq!(concurrency.on_success(), 3);
+        for _ in 0..100 {
+            concurrency.on_success();
+        }
+        assert_eq!(concurrency.limit(), 8);
+    }
+}
@@ -1,46 +1,94 @@
-use std::collections::HashSet;
+use std::ops::RangeInclusive;
 use anyhow::{Result, anyhow};
 
-/// 从用户输入提取数字（支持空格分隔和 a-b 范围格式），并自动去重
-/// 输入示例："1 3-5 3 7-9 8" → 输出：1, 3, 4, 5, 7, 8, 9
-pub fn get_index_from_stdin(input: &str) -> Result<Vec<u8>> {
-    let mut result = HashSet::new(); // 使用HashSet自动去重
+use crate::core::options::VolumeSelection;
+use crate::utils::terminal::clear_previous_line;
 
-    for token in input.split_whitespace() {
+/// 从用户输入提取序号的范围（支持空格或逗号分隔和 a-b 范围格式），合并重复和相邻的范围并排序
+/// 输入示例："1 3-5 3 7-9,8" → 输出：1, 3-5, 7-9
+///
+/// 范围不会展开成序号，使用时再按卷或章节的实际数量截断，输入很大的范围也不会占用内存
+pub fn get_index_from_stdin(input: &str) -> Result<Vec<RangeInclusive<usize>>> {
+    let mut result = Vec::new();
+
+    for token in input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|t| !t.is_empty())
+    {
         if token.contains('-') {
             // 处理范围格式（a-b）
             let parts: Vec<&str> = token.split('-').collect();
             if parts.len() != 2 {
-                return Err(anyhow!("效的范围格式: {}", token));
+                return Err(anyhow!("无效的范围格式: {}", token));
             }
 
-            let a = parts[0].parse::<u8>().map_err(|e| {
+            let a = parts[0].parse::<usize>().map_err(|e| {
                 anyhow!("范围起始不是有效数字 '{}': {}", parts[0], e)
             })?;
-            let b = parts[1].parse::<u8>().map_err(|e| {
+            let b = parts[1].parse::<usize>().map_err(|e| {
                 anyhow!("范围结束不是有效数字 '{}': {}", parts[1], e)
             })?;
 
             if a > b {
                 return Err(anyhow!("范围无效（起始 > 结束）: {}-{}", a, b));
             }
-
-            // 添加范围内的所有数字（HashSet会自动去重）
-            for num in a..=b {
-                result.insert(num);
-            }
+            result.push(a..=b);
         } else {
             // 处理单个数字
-            let num = token.parse::<u8>().map_err(|e| {
+            let num = token.parse::<usize>().map_err(|e| {
                 anyhow!("不是有效数字 '{}': {}", token, e)
             })?;
-            result.insert(num); // 重复数字会被自动忽略
+            result.push(num..=num);
         }
     }
 
-    // 将HashSet转换为Vec并排序（可选）
-    let mut vec_result: Vec<u8> = result.into_iter().collect();
-    vec_result.sort(); // 排序使结果更直观
+    Ok(merge_ranges(result))
+}
 
-    Ok(vec_result)
-}
\ No newline at end of file
+/// 排序并合并重叠或相邻的范围
+pub fn merge_ranges(mut ranges: Vec<RangeInclusive<usize>>) -> Vec<RangeInclusive<usize>> {
+    ranges.sort_by_key(|r| (*r.start(), *r.end()));
+    let mut merged: Vec<RangeInclusive<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if *range.start() <= last.end().saturating_add(1) => {
+                *last = *last.start()..=*last.end().max(range.end());
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+///从输入读取要下载的卷
+pub fn read_index_from_stdin() -> VolumeSelection {
+    println!("\n");
+    loop {
+        println!(
+            "输入你要下载的卷[支持空格分隔、a-b 范围、负数倒数、all、last、new 和卷名中的文字]:"
+        );
+        let mut input = String::new();
+        match std::io::stdin().read_line(&mut input) {
+            Ok(_bytes_read) => {
+                let trimmed = input.trim().to_string();
+                if trimmed.is_empty() {
+                    clear_previous_line(2).unwrap_or_else(|e| eprintln!("清除屏幕失败：{}", e));
+                    continue;
+                }
+                match VolumeSelection::parse(&trimmed) {
+                    Ok(selection) => break selection,
+                    Err(e) => {
+                        clear_previous_line(3)
+                            .unwrap_or_else(|e| eprintln!("清除屏幕失败：{}", e));
+                        eprintln!("{}", e)
+                    }
+                }
+            }
+            Err(e) => {
+                clear_previous_line(3).unwrap_or_else(|e| eprintln!("清除屏幕失败：{}", e));
+                // 读取失败，打印错误详情（而非终止程序）
+                eprintln!("读取输入出错：{}", e);
+            }
+        }
+    }
+}
@@ -1,29 +1,21 @@
-use crate::core::{init::URL_HANDLERS, singlefile::Singlefile};
+use crate::core::init::{resolve_url, supported_sites};
+use crate::core::pipeline::Pipeline;
 use crate::utils::terminal::clear_previous_line;
-use anyhow::{Result, anyhow};
-use url::Url;
+use anyhow::Result;
 
-/// 获取url对应的结构体
-pub fn get_struct_by_url(url: &str) -> Result<Box<dyn Singlefile>> {
-    let parsed_url = Url::parse(url).map_err(|e| anyhow!("URL解析失败: {}", e))?;
-
-    let host = parsed_url
-        .host_str()
-        .ok_or_else(|| anyhow!("无法获取主机名: {}", url))?;
-
-    let handlers = URL_HANDLERS.lock().unwrap();
-
-    if let Some(handler) = handlers.get(host) {
-        return Ok(handler(url.to_string()));
-    }
-
-    Err(anyhow!("不支持的网站: {}", host))
+/// 获取url对应的结构体，支持书籍、卷和章节的链接
+pub fn get_struct_by_url(url: &str) -> Result<Pipeline> {
+    let (canonical, handler) = resolve_url(url)?;
+    Ok(Pipeline::new(handler(canonical)?))
 }
 
 ///从输入读取url
 pub fn read_url_from_stdin() -> String {
-    let use_input = loop {
-        println!("请输入URL(回车确定)[目前只支持哔哩轻小说]:");
+    loop {
+        println!(
+            "请输入URL(回车确定)[支持的网站：{}]:",
+            supported_sites().join("、")
+        );
         let mut input = String::new();
         match std::io::stdin().read_line(&mut input) {
             Ok(_bytes_read) => {
@@ -44,22 +36,21 @@ pub fn read_url_from_stdin() -> String {
             }
         }
        
-    };
-    use_input
+    }
 }
 
 ///返回url对应的结构体
-pub fn get_from_url() -> Box<dyn Singlefile> {
+pub fn get_from_url() -> Pipeline {
     // let mut stdout = std::io::stdout();
     println!("\n");
-    let res = loop {
+    loop {
         let input = read_url_from_stdin();
         match get_struct_by_url(&input) {
             Ok(s) => {
                 //清除屏幕
                 // clear_previous_line(1).unwrap_or_else(|e| eprintln!("清除屏幕失败：{}", e));
                 println!("\n加载数据中……\n");
-                break s;
+                return s;
             }
             Err(e) => {
                 // 清除屏幕
@@ -67,6 +58,5 @@ pub fn get_from_url() -> Box<dyn Singlefile> {
                 println!("{e}");
             }
         }
-    };
-    res
+    }
 }
@@ -1,27 +1,198 @@
-use crate::{core::singlefile::Singlefile, source::bilinovel::parser::get_bilinovel};
+use crate::core::site::{SiteDefinition, load_site_definitions};
+use crate::source::generic::source::GenericSource;
+use crate::{core::source::Source, source::bilinovel::parser::get_bilinovel};
+use anyhow::{Result, anyhow};
 use once_cell::sync::Lazy;
-use std::{collections::HashMap, sync::Mutex};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+use url::Url;
 
-/// 全局注册表
-pub static URL_HANDLERS: Lazy<Mutex<HashMap<String, fn(String) -> Box<dyn Singlefile>>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+/// 根据url创建对应结构体的函数，参数为规范化后的书籍链接
+pub type UrlHandler = Arc<dyn Fn(String) -> Result<Box<dyn Source>> + Send + Sync>;
 
-/// 注册 URL 处理器
-pub fn register_url_handler(domain: &str, handler: fn(String) -> Box<dyn Singlefile>) {
+/// 网站定义所在的目录
+pub const SITES_DIR: &str = "./config/sites";
+
+/// 网站的链接规则
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UrlPattern {
+    /// 主机名通配符，`*` 匹配任意字符，`*.example.com` 同时匹配 `example.com`
+    pub hosts: Vec<String>,
+    /// 路径正则，命名捕获组 `id`（没有时为第一个捕获组）为书籍号
+    pub path: String,
+    /// 书籍的规范链接，`{id}` 会被替换为书籍号
+    pub canonical: String,
+}
+
+/// 注册表中的一项
+pub struct UrlRule {
+    /// 网站名，显示在输入提示中
+    name: String,
+    pattern: UrlPattern,
+    path: Regex,
+    handler: UrlHandler,
+}
+
+/// 全局注册表，按注册顺序匹配
+pub static URL_HANDLERS: Lazy<Mutex<Vec<UrlRule>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// 注册 URL 处理器，同名的网站会被替换
+pub fn register_url_handler<F>(name: &str, pattern: UrlPattern, handler: F) -> Result<()>
+where
+    F: Fn(String) -> Result<Box<dyn Source>> + Send + Sync + 'static,
+{
+    let path = Regex::new(&pattern.path)
+        .map_err(|e| anyhow!("{}的路径正则无效 {}：{}", name, pattern.path, e))?;
+    let rule = UrlRule {
+        name: name.to_string(),
+        pattern,
+        path,
+        handler: Arc::new(handler),
+    };
+    let mut handlers = URL_HANDLERS.lock().unwrap();
+    match handlers.iter_mut().find(|r| r.name == name) {
+        Some(existing) => *existing = rule,
+        None => handlers.push(rule),
+    }
+    Ok(())
+}
+
+/// 已注册的网站名
+pub fn supported_sites() -> Vec<String> {
     URL_HANDLERS
         .lock()
         .unwrap()
-        .insert(domain.to_string(), handler);
+        .iter()
+        .map(|r| r.name.clone())
+        .collect()
+}
+
+/// 找到链接对应的处理器，并将书籍、卷或章节的链接规范化为书籍链接
+pub fn resolve_url(url: &str) -> Result<(String, UrlHandler)> {
+    let parsed_url = Url::parse(url.trim()).map_err(|e| anyhow!("URL解析失败: {}", e))?;
+    let host = parsed_url
+        .host_str()
+        .ok_or_else(|| anyhow!("无法获取主机名: {}", url))?;
+
+    let handlers = URL_HANDLERS.lock().unwrap();
+    let mut host_supported = false;
+    for rule in handlers.iter() {
+        if !rule.pattern.hosts.iter().any(|p| host_matches(p, host)) {
+            continue;
+        }
+        host_supported = true;
+        if let Some(canonical) = rule.canonicalize(parsed_url.path()) {
+            return Ok((canonical, rule.handler.clone()));
+        }
+    }
+
+    if host_supported {
+        Err(anyhow!("无法识别的链接: {}", url))
+    } else {
+        Err(anyhow!("不支持的网站: {}", host))
+    }
+}
+
+impl UrlRule {
+    /// 从路径提取书籍号，得到规范链接
+    fn canonicalize(&self, path: &str) -> Option<String> {
+        let captures = self.path.captures(path)?;
+        let id = captures.name("id").or_else(|| captures.get(1))?.as_str();
+        Some(self.pattern.canonical.replace("{id}", id))
+    }
+}
+
+/// 主机名是否匹配通配符，不区分大小写
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    if let Some(domain) = pattern.strip_prefix("*.")
+        && host == domain
+    {
+        return true;
+    }
+    wildcard_match(pattern.as_bytes(), host.as_bytes())
+}
+
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| wildcard_match(rest, &text[i..])),
+        Some((c, rest)) => text
+            .split_first()
+            .is_some_and(|(t, text)| t == c && wildcard_match(rest, text)),
+    }
 }
 
 /// 初始化注册表（可以在程序启动时调用）
 pub fn init_url_handlers() {
-    register_url_handler("www.linovelib.com", |url| get_bilinovel(&url));
-    register_url_handler("linovelib.com", |url| get_bilinovel(&url));
-    // 注册更多处理器...
+    let mut sites = load_site_definitions(SITES_DIR);
+    if !sites.iter().any(|s| s.parser == "bilinovel") {
+        info!("没有找到哔哩轻小说的网站定义，使用内置定义");
+        sites.insert(0, SiteDefinition::bilinovel());
+    }
+    for site in sites {
+        register_site(site).unwrap_or_else(|e| error!("注册处理器失败：{}", e));
+    }
+}
+
+/// 按网站定义注册处理器，`parser` 决定使用的解析方式
+pub fn register_site(site: SiteDefinition) -> Result<()> {
+    let name = site.name.clone();
+    let pattern = site.url.clone();
+    let site = Arc::new(site);
+    match site.parser.as_str() {
+        "bilinovel" => register_url_handler(&name, pattern, move |url| {
+            Ok(get_bilinovel(&url, site.clone())?)
+        }),
+        "generic" => register_url_handler(&name, pattern, move |url| {
+            Ok(Box::new(GenericSource::new(url, site.clone())?))
+        }),
+        other => Err(anyhow!("{}的解析方式未知：{}", name, other)),
+    }
 }
 
 ///初始化注册表
 pub fn init_url_parser() {
     init_url_handlers();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_matches() {
+        assert!(host_matches("*.linovelib.com", "www.linovelib.com"));
+        assert!(host_matches("*.linovelib.com", "m.linovelib.com"));
+        assert!(host_matches("*.linovelib.com", "linovelib.com"));
+        assert!(host_matches("*.linovelib.com", "WWW.Linovelib.com"));
+        assert!(host_matches("www.*.com", "www.bilinovel.com"));
+        assert!(!host_matches("*.linovelib.com", "linovelib.com.evil.org"));
+        assert!(!host_matches("*.linovelib.com", "notlinovelib.com"));
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        init_url_handlers();
+        let canonical = "https://www.linovelib.com/novel/2356.html";
+        for url in [
+            "https://www.linovelib.com/novel/2356.html",
+            "https://m.linovelib.com/novel/2356.html",
+            "https://www.bilinovel.com/novel/2356/catalog",
+            "https://www.linovelib.com/novel/2356/vol_86523.html",
+            "https://www.linovelib.com/novel/2356/86524_2.html",
+        ] {
+            let (resolved, _) = resolve_url(url).unwrap();
+            assert_eq!(resolved, canonical, "{}", url);
+        }
+
+        let err = resolve_url("https://www.linovelib.com/top/").err().unwrap();
+        assert!(err.to_string().contains("无法识别的链接"));
+        let err = resolve_url("https://example.com/novel/1.html").err().unwrap();
+        assert!(err.to_string().contains("不支持的网站"));
+        assert!(supported_sites().contains(&"哔哩轻小说".to_string()));
+    }
+}
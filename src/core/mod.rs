@@ -1,4 +1,14 @@
-pub mod singlefile;
 pub mod get_struct;
 pub mod init;
-pub mod get_index;
\ No newline at end of file
+pub mod get_index;
+pub mod options;
+pub mod report;
+pub mod batch;
+pub mod model;
+pub mod check;
+pub mod source;
+pub mod state;
+pub mod pipeline;
+pub mod site;
+pub mod concurrency;
+pub mod output;
@@ -0,0 +1,198 @@
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 书籍的通用模型，各个网站的解析结果都转换为这个模型，打包和检查只使用这个模型
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Book {
+    /// 来源网站，如 bilinovel
+    pub source: String,
+    /// 书籍在来源网站的编号
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    pub metadata: Metadata,
+    pub volumes: Vec<Volume>,
+}
+
+/// 卷，同时也是下载状态文件的内容
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Volume {
+    /// 卷在来源网站的编号，没有时为空
+    pub id: String,
+    pub url: String,
+    pub name: String,
+    pub cover: Option<ImageRef>,
+    pub metadata: Metadata,
+    pub chapters: Vec<Chapter>,
+    /// 还没有下载完成的章节序号
+    pub pending_chapter_indices: Vec<usize>,
+}
+
+/// 章节
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chapter {
+    pub url: String,
+    pub title: String,
+    /// 每一页的html，下载失败的页为空
+    #[serde(alias = "context")]
+    pub pages: Vec<String>,
+    #[serde(alias = "image")]
+    pub images: Vec<ImageRef>,
+}
+
+/// 图片引用，图片数据保存在图片存储中，通过链接查找
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ImageRef {
+    pub url: String,
+}
+
+/// 书籍或卷的元数据
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    pub author: String,
+    pub illustrator: Option<String>,
+    pub publisher: Option<String>,
+    /// 语言代码，如 zh-CN
+    pub language: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// 所属系列
+    pub series: Option<String>,
+    /// 在系列中的序号，从1开始
+    pub series_index: Option<u32>,
+}
+
+impl Book {
+    pub fn new(source: &str, id: &str, url: &str, title: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            id: id.to_string(),
+            url: url.to_string(),
+            title: title.to_string(),
+            ..Book::default()
+        }
+    }
+}
+
+impl Volume {
+    pub fn new(url: &str, name: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            name: name.to_string(),
+            ..Volume::default()
+        }
+    }
+
+    /// 卷缺少的元数据从书籍补全
+    pub fn inherit(&mut self, book: &Book) {
+        self.metadata.inherit(&book.metadata);
+    }
+
+    /// 卷引用的所有图片，包括封面
+    pub fn images(&self) -> impl Iterator<Item = &ImageRef> {
+        self.cover
+            .iter()
+            .chain(self.chapters.iter().flat_map(|c| c.images.iter()))
+    }
+}
+
+impl Chapter {
+    pub fn new(url: &str, title: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            title: title.to_string(),
+            ..Chapter::default()
+        }
+    }
+
+    /// 是否有缺页
+    pub fn has_missing_pages(&self) -> bool {
+        self.pages.is_empty() || self.pages.iter().any(|p| p.is_empty())
+    }
+
+    /// 按标题判断是否为插图章节
+    pub fn is_illustration(&self) -> bool {
+        ["插图", "插画", "彩插", "彩页", "插圖", "插畫"]
+            .iter()
+            .any(|word| self.title.contains(word))
+    }
+
+    /// 按标题判断是否为后记
+    pub fn is_afterword(&self) -> bool {
+        let title = self.title.to_lowercase();
+        ["后记", "後記", "あとがき", "afterword"]
+            .iter()
+            .any(|word| title.contains(word))
+    }
+}
+
+impl ImageRef {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+        }
+    }
+}
+
+impl From<String> for ImageRef {
+    fn from(url: String) -> Self {
+        Self { url }
+    }
+}
+
+impl Metadata {
+    /// 用另一份元数据补全缺少的字段
+    pub fn inherit(&mut self, other: &Metadata) {
+        fn fill(target: &mut String, value: &str) {
+            if target.is_empty() {
+                *target = value.to_string();
+            }
+        }
+        fill(&mut self.author, &other.author);
+        fill(&mut self.language, &other.language);
+        fill(&mut self.description, &other.description);
+        if self.tags.is_empty() {
+            self.tags = other.tags.clone();
+        }
+        self.illustrator = self
+            .illustrator
+            .take()
+            .or_else(|| other.illustrator.clone());
+        self.publisher = self.publisher.take().or_else(|| other.publisher.clone());
+        self.series = self.series.take().or_else(|| other.series.clone());
+        self.series_index = self.series_index.or(other.series_index);
+    }
+
+    /// 语言，没有设置时为 zh-CN
+    pub fn language(&self) -> &str {
+        if self.language.is_empty() {
+            "zh-CN"
+        } else {
+            &self.language
+        }
+    }
+}
+
+impl fmt::Display for Book {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}\n", self.title.bright_yellow().bold())?;
+        if !self.metadata.author.is_empty() {
+            writeln!(f, "{}\n", self.metadata.author.blue())?;
+        }
+        if !self.metadata.tags.is_empty() {
+            writeln!(f, "{}\n", self.metadata.tags.join("  "))?;
+        }
+        if !self.metadata.description.is_empty() {
+            writeln!(f, "{}\n", self.metadata.description)?;
+        }
+        for (i, v) in self.volumes.iter().enumerate() {
+            writeln!(f, "[{}]\t{}", i, v.name.underline())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use tracing::warn;
+
+use crate::core::get_index::{get_index_from_stdin, merge_ranges};
+use crate::core::model::{Chapter, Volume};
+
+/// 要处理的卷
+#[derive(Debug, Clone, PartialEq)]
+pub enum VolumeSelection {
+    /// 所有卷
+    All,
+    /// 指定序号范围的卷，已排序并合并
+    Indices(Vec<RangeInclusive<usize>>),
+    /// 按关键字、倒数的序号或卷名选择，结果为各项的并集
+    Items(Vec<VolumeItem>),
+}
+
+/// 选择卷的一项
+#[derive(Debug, Clone, PartialEq)]
+pub enum VolumeItem {
+    /// 序号，负数从最后一卷倒数，-1 为最后一卷
+    Index(isize),
+    /// 序号的范围
+    Range(RangeInclusive<usize>),
+    /// 还没有打包的卷
+    New,
+    /// 卷名包含的文字
+    Name(String),
+    /// 匹配卷名的正则表达式
+    Regex(String),
+}
+
+impl fmt::Display for VolumeItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VolumeItem::Index(i) => write!(f, "{}", i),
+            VolumeItem::Range(range) => write!(f, "{}", format_range(range)),
+            VolumeItem::New => write!(f, "new"),
+            VolumeItem::Name(name) => write!(f, "{}", name),
+            VolumeItem::Regex(pattern) => write!(f, "/{}/", pattern),
+        }
+    }
+}
+
+impl VolumeSelection {
+    /// 解析卷的选择，用空格或逗号分隔多项：
+    /// 序号和 a-b 范围格式同 [`get_index_from_stdin`]，`-1` 为最后一卷，
+    /// `all` 为所有卷，`last` 为最后一卷，`new` 为还没有打包的卷，
+    /// `/正则/` 匹配卷名，其他文字选择卷名包含这些文字的卷
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut indices = Vec::new();
+        let mut items = Vec::new();
+        for token in split_tokens(input) {
+            match token.to_ascii_lowercase().as_str() {
+                "all" => return Ok(VolumeSelection::All),
+                "last" => items.push(VolumeItem::Index(-1)),
+                "new" => items.push(VolumeItem::New),
+                _ if token.len() > 2 && token.starts_with('/') && token.ends_with('/') => {
+                    let pattern = &token[1..token.len() - 1];
+                    Regex::new(pattern)
+                        .map_err(|e| anyhow!("无效的正则表达式 '{}': {}", pattern, e))?;
+                    items.push(VolumeItem::Regex(pattern.to_string()));
+                }
+                _ if token.starts_with('-') => {
+                    let n = token[1..]
+                        .parse::<isize>()
+                        .map_err(|e| anyhow!("不是有效的倒数序号 '{}': {}", token, e))?;
+                    if n <= 0 {
+                        return Err(anyhow!("倒数序号从-1开始: {}", token));
+                    }
+                    items.push(VolumeItem::Index(-n));
+                }
+                _ if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                    indices.extend(get_index_from_stdin(token)?);
+                }
+                _ => items.push(VolumeItem::Name(token.to_string())),
+            }
+        }
+        let indices = merge_ranges(indices);
+        if items.is_empty() {
+            Ok(VolumeSelection::Indices(indices))
+        } else {
+            Ok(VolumeSelection::Items(
+                indices
+                    .into_iter()
+                    .map(VolumeItem::Range)
+                    .chain(items)
+                    .collect(),
+            ))
+        }
+    }
+
+    /// 合并两个选择
+    pub fn union(self, other: VolumeSelection) -> VolumeSelection {
+        match (self, other) {
+            (VolumeSelection::All, _) | (_, VolumeSelection::All) => VolumeSelection::All,
+            (VolumeSelection::Indices(mut a), VolumeSelection::Indices(b)) => {
+                a.extend(b);
+                VolumeSelection::Indices(merge_ranges(a))
+            }
+            (a, b) => VolumeSelection::Items(a.into_items().chain(b.into_items()).collect()),
+        }
+    }
+
+    fn into_items(self) -> impl Iterator<Item = VolumeItem> {
+        let items = match self {
+            VolumeSelection::All => Vec::new(),
+            VolumeSelection::Indices(index) => index.into_iter().map(VolumeItem::Range).collect(),
+            VolumeSelection::Items(items) => items,
+        };
+        items.into_iter()
+    }
+
+    /// 根据卷列表得到要处理的序号，`is_new` 判断卷是否还没有打包
+    ///
+    /// 超出范围的单个序号会保留，由调用者提示卷不存在，范围按卷的数量截断
+    pub fn resolve<F>(&self, volumes: &[Volume], is_new: F) -> Vec<usize>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let count = volumes.len();
+        let items = match self {
+            VolumeSelection::All => return (0..count).collect(),
+            VolumeSelection::Indices(_) => self.clone().into_items().collect(),
+            VolumeSelection::Items(items) => items.clone(),
+        };
+        let mut result = Vec::new();
+        for item in &items {
+            let before = result.len();
+            match item {
+                VolumeItem::Index(i) if *i >= 0 => result.push(*i as usize),
+                VolumeItem::Range(range) => result.extend(clamp_range(range, count)),
+                VolumeItem::Index(i) => result.extend(count.checked_sub(i.unsigned_abs())),
+                VolumeItem::New => result.extend((0..count).filter(|&i| is_new(i))),
+                VolumeItem::Name(name) => result.extend(
+                    volumes
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, v)| v.name.contains(name.as_str()))
+                        .map(|(i, _)| i),
+                ),
+                VolumeItem::Regex(pattern) => {
+                    if let Ok(re) = Regex::new(pattern) {
+                        result.extend(
+                            volumes
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, v)| re.is_match(&v.name))
+                                .map(|(i, _)| i),
+                        );
+                    }
+                }
+            }
+            if result.len() == before {
+                warn!("没有符合 {} 的卷", item);
+                eprintln!("没有符合 {} 的卷", item);
+            }
+        }
+        result.sort();
+        result.dedup();
+        result
+    }
+}
+
+/// 用空格或逗号分隔输入，`/正则/` 作为一项，其中可以有空格和逗号
+fn split_tokens(input: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+        let end = match rest.strip_prefix('/').and_then(|body| body.find('/')) {
+            Some(i) => i + 2,
+            None => rest
+                .find(|c: char| c.is_whitespace() || c == ',')
+                .unwrap_or(rest.len()),
+        };
+        tokens.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    tokens
+}
+
+/// 单个序号原样保留，范围截断到 `count` 以内
+fn clamp_range(range: &RangeInclusive<usize>, count: usize) -> Range<usize> {
+    let end = range.end().saturating_add(1);
+    if range.start() == range.end() {
+        *range.start()..end
+    } else {
+        *range.start()..end.min(count)
+    }
+}
+
+/// 如 `3` 或 `3-7`
+fn format_range(range: &RangeInclusive<usize>) -> String {
+    if range.start() == range.end() {
+        range.start().to_string()
+    } else {
+        format!("{}-{}", range.start(), range.end())
+    }
+}
+
+/// 卷中要处理的章节
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChapterSelection {
+    /// 章节序号的范围，和章节列表中显示的相同，为 None 时选择所有章节
+    pub indices: Option<Vec<RangeInclusive<usize>>>,
+    /// 跳过插图章节
+    pub skip_illustrations: bool,
+    /// 跳过后记
+    pub skip_afterword: bool,
+}
+
+impl ChapterSelection {
+    /// 按序号选择章节，格式同 [`get_index_from_stdin`]
+    pub fn parse(input: &str) -> Result<Self> {
+        Ok(Self {
+            indices: Some(get_index_from_stdin(input)?),
+            ..Self::default()
+        })
+    }
+
+    /// 是否选择了所有章节
+    pub fn is_all(&self) -> bool {
+        self.indices.is_none() && !self.skip_illustrations && !self.skip_afterword
+    }
+
+    /// 根据章节列表得到要处理的序号，超出范围的序号会被忽略
+    pub fn resolve(&self, chapters: &[Chapter]) -> Vec<usize> {
+        let indices = match &self.indices {
+            Some(indices) => {
+                for range in indices.iter().filter(|r| *r.start() >= chapters.len()) {
+                    warn!("第{}章不存在，跳过", format_range(range));
+                    eprintln!("第{}章不存在，跳过", format_range(range));
+                }
+                indices
+                    .iter()
+                    .flat_map(|r| clamp_range(r, chapters.len()))
+                    .filter(|&i| i < chapters.len())
+                    .collect()
+            }
+            None => (0..chapters.len()).collect::<Vec<_>>(),
+        };
+        indices
+            .into_iter()
+            .filter(|&i| !(self.skip_illustrations && chapters[i].is_illustration()))
+            .filter(|&i| !(self.skip_afterword && chapters[i].is_afterword()))
+            .collect()
+    }
+}
+
+impl fmt::Display for ChapterSelection {
+    /// 如 `第3-7,12章，不含插图、后记`，用于卷名
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(indices) = &self.indices {
+            let ranges: Vec<String> = indices.iter().map(format_range).collect();
+            parts.push(format!("第{}章", ranges.join(",")));
+        }
+        let skipped: Vec<&str> = [
+            (self.skip_illustrations, "插图"),
+            (self.skip_afterword, "后记"),
+        ]
+        .iter()
+        .filter(|(skip, _)| *skip)
+        .map(|(_, name)| *name)
+        .collect();
+        if !skipped.is_empty() {
+            parts.push(format!("不含{}", skipped.join("、")));
+        }
+        write!(f, "{}", parts.join("，"))
+    }
+}
+
+/// 下载流程的选项
+///
+/// 取值为 `None` 的字段表示运行时在终端询问用户，命令行模式下由参数预先给出答案
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// 要处理的卷
+    pub volumes: Option<VolumeSelection>,
+    /// 每一卷中要处理的章节，为 None 时处理整卷
+    pub chapters: Option<ChapterSelection>,
+    /// 是否下载章节，为 false 时只使用已保存的下载数据
+    pub download: bool,
+    /// 发现未完成的下载时是否恢复
+    pub resume: Option<bool>,
+    /// 下载完成后是否检查章节
+    pub check: Option<bool>,
+    /// 是否打包epub
+    pub pack: Option<bool>,
+    /// 打包成功后是否删除下载数据
+    pub clean: Option<bool>,
+    /// 将所有卷打包为一本合集，而不是每卷一本
+    pub omnibus: bool,
+    /// epub中的系列名，为 None 时使用书名
+    pub series: Option<String>,
+    /// 在系列中的序号，选择多卷时为第一卷的序号，之后的卷依次加一，为 None 时使用卷的序号加一
+    pub series_index: Option<u32>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            volumes: None,
+            chapters: None,
+            download: true,
+            resume: None,
+            check: None,
+            pack: None,
+            clean: None,
+            omnibus: false,
+            series: None,
+            series_index: None,
+        }
+    }
+}
+
+impl DownloadOptions {
+    /// 交互模式，所有问题都在运行时询问
+    pub fn interactive() -> Self {
+        Self::default()
+    }
+
+    /// 设置要处理的卷
+    pub fn with_volumes(mut self, volumes: VolumeSelection) -> Self {
+        self.volumes = Some(volumes);
+        self
+    }
+
+    /// 设置每一卷中要处理的章节，选择所有章节时处理整卷
+    pub fn with_chapters(mut self, chapters: ChapterSelection) -> Self {
+        self.chapters = (!chapters.is_all()).then_some(chapters);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_volumes() {
+        let volumes: Vec<Volume> = ["第一卷", "第二卷", "短篇集", "第三卷 下"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Volume::new(&format!("https://example.com/vol_{}.html", i), name))
+            .collect();
+        let resolve = |input: &str| {
+            VolumeSelection::parse(input)
+                .unwrap()
+                .resolve(&volumes, |i| i >= 2)
+        };
+
+        assert_eq!(
+            VolumeSelection::parse("1 3-5,300 4").unwrap(),
+            VolumeSelection::Indices(vec![1..=1, 3..=5, 300..=300])
+        );
+        assert_eq!(resolve("1-99999999999"), vec![1, 2, 3]);
+        assert_eq!(resolve("8-9"), Vec::<usize>::new());
+        assert_eq!(resolve("all"), vec![0, 1, 2, 3]);
+        assert_eq!(resolve("last"), vec![3]);
+        assert_eq!(resolve("0 -2"), vec![0, 2]);
+        assert_eq!(resolve("-9"), Vec::<usize>::new());
+        assert_eq!(resolve("new"), vec![2, 3]);
+        assert_eq!(resolve("短篇"), vec![2]);
+        assert_eq!(resolve("/^第.卷$/ last"), vec![0, 1, 3]);
+        assert_eq!(resolve("/^第.{1,3}卷/,0"), vec![0, 1, 3]);
+        assert!(VolumeSelection::parse("-0").is_err());
+        assert!(VolumeSelection::parse("/[/").is_err());
+    }
+
+    #[test]
+    fn test_select_chapters() {
+        let chapters: Vec<Chapter> = ["插图", "序章", "第一章", "第二章", "第三章", "后记"]
+            .iter()
+            .map(|title| Chapter::new(&format!("https://example.com/{}", title), title))
+            .collect();
+
+        let mut selection = ChapterSelection::parse("0-2,4,9,1").unwrap();
+        assert_eq!(selection.to_string(), "第0-2,4,9章");
+        assert_eq!(selection.resolve(&chapters), vec![0, 1, 2, 4]);
+        selection.skip_illustrations = true;
+        assert_eq!(selection.resolve(&chapters), vec![1, 2, 4]);
+
+        let selection = ChapterSelection {
+            skip_illustrations: true,
+            skip_afterword: true,
+            ..ChapterSelection::default()
+        };
+        assert_eq!(selection.to_string(), "不含插图、后记");
+        assert_eq!(selection.resolve(&chapters), vec![1, 2, 3, 4]);
+        assert!(ChapterSelection::default().is_all());
+        let selection = ChapterSelection::parse("4-99999999999").unwrap();
+        assert_eq!(selection.resolve(&chapters), vec![4, 5]);
+    }
+}
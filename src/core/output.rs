@@ -0,0 +1,264 @@
+//! 输出文件名模板
+//!
+//! 模板中的 `{变量}` 会被替换，`{变量:03}` 将数字补零到3位，`/` 用于分隔目录。
+//! 替换后的每一级路径都会去掉非法字符并限制长度，和本次运行生成的文件或输出目录中已有的文件重名时会加上序号。
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow, bail};
+use once_cell::sync::Lazy;
+
+use crate::core::model::{Book, Volume};
+
+/// 默认的输出文件名模板
+pub const DEFAULT_TEMPLATE: &str = "{volume}.epub";
+
+/// 每一级路径的最大字符数
+const MAX_COMPONENT_CHARS: usize = 120;
+
+/// 本次运行已经生成的文件
+static CLAIMED: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// 模板中可用的变量
+#[derive(Debug, Clone)]
+pub struct OutputVars<'a> {
+    /// 书名
+    pub book: &'a str,
+    /// 卷名
+    pub volume: &'a str,
+    pub author: &'a str,
+    /// 来源网站
+    pub source: &'a str,
+    /// 书籍号
+    pub id: &'a str,
+    /// 卷的序号，和卷列表中显示的相同，从0开始
+    pub index: usize,
+}
+
+impl<'a> OutputVars<'a> {
+    pub fn new(book: &'a Book, volume: &'a Volume, index: usize) -> Self {
+        let author = if volume.metadata.author.is_empty() {
+            &book.metadata.author
+        } else {
+            &volume.metadata.author
+        };
+        Self {
+            book: &book.title,
+            volume: &volume.name,
+            author,
+            source: &book.source,
+            id: &book.id,
+            index,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<Value<'a>> {
+        Some(match name {
+            "book" => Value::Text(self.book),
+            "volume" => Value::Text(self.volume),
+            "author" => Value::Text(self.author),
+            "source" => Value::Text(self.source),
+            "id" => Value::Text(self.id),
+            "index" => Value::Number(self.index),
+            // 从1开始的序号
+            "number" => Value::Number(self.index + 1),
+            _ => return None,
+        })
+    }
+}
+
+enum Value<'a> {
+    Text(&'a str),
+    Number(usize),
+}
+
+/// 按模板得到输出文件在输出目录中的相对路径
+pub fn render(template: &str, vars: &OutputVars) -> Result<PathBuf> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("输出模板缺少 }}：{}", template))?;
+        let field = &rest[start + 1..start + end];
+        let (name, spec) = field.split_once(':').unwrap_or((field, ""));
+        let value = vars
+            .get(name)
+            .ok_or_else(|| anyhow!("输出模板中的变量未知：{{{}}}", name))?;
+        match value {
+            // 变量中的 `/` 不作为目录分隔
+            Value::Text(text) => rendered.push_str(&text.replace(['/', '\\'], "_")),
+            Value::Number(n) => {
+                let width = match spec.strip_prefix('0').unwrap_or(spec) {
+                    "" => 0,
+                    width => width
+                        .parse()
+                        .map_err(|_| anyhow!("输出模板中的格式无效：{{{}}}", field))?,
+                };
+                rendered.push_str(&format!("{:0width$}", n, width = width));
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+
+    let path: PathBuf = rendered
+        .split(['/', '\\'])
+        .filter(|c| !c.trim().is_empty())
+        .map(sanitize_component)
+        .collect();
+    if path.as_os_str().is_empty() {
+        bail!("输出模板得到的文件名为空：{}", template);
+    }
+    Ok(path)
+}
+
+/// 去掉一级路径中的非法字符，避开Windows的保留名并限制长度
+fn sanitize_component(component: &str) -> String {
+    let mut name: String = component
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    name = name.trim().trim_end_matches('.').to_string();
+    if name.is_empty() || name.chars().all(|c| c == '.') {
+        return "_".to_string();
+    }
+
+    // 超长时截断主文件名，保留扩展名
+    if name.chars().count() > MAX_COMPONENT_CHARS {
+        let (stem, ext) = match name.rfind('.') {
+            Some(dot) if name.len() - dot <= 10 => name.split_at(dot),
+            _ => (name.as_str(), ""),
+        };
+        let keep = MAX_COMPONENT_CHARS.saturating_sub(ext.chars().count());
+        name = stem
+            .chars()
+            .take(keep)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+            + ext;
+    }
+
+    let stem = name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || (stem.len() == 4
+            && (stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.as_bytes()[3].is_ascii_digit());
+    if reserved {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// 路径没有被使用时原样返回，否则在文件名后加上 ` (2)`、` (3)` 等
+///
+/// 本次运行已经生成的文件总是算作重名，`overwrite` 为 false 时磁盘上已有的文件也算作重名
+pub fn available_path(path: &Path, overwrite: bool) -> PathBuf {
+    let claimed = CLAIMED.lock().unwrap();
+    let used = |p: &Path| claimed.contains(p) || (!overwrite && p.exists());
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut candidate = path.to_path_buf();
+    let mut n = 1;
+    while used(&candidate) {
+        n += 1;
+        candidate = path.with_file_name(format!("{} ({}){}", stem, n, ext));
+    }
+    candidate
+}
+
+/// 记录本次运行生成的文件，在文件写入成功后调用，之后同名的文件会加上序号
+pub fn claim(path: &Path) {
+    CLAIMED.lock().unwrap().insert(path.to_path_buf());
+}
+
+/// 按模板得到输出文件的路径并创建所在的目录，文件写入成功后需要调用 [`claim`]
+pub fn output_path(
+    dir: &str,
+    template: &str,
+    vars: &OutputVars,
+    overwrite: bool,
+) -> Result<PathBuf> {
+    let path = available_path(&Path::new(dir).join(render(template, vars)?), overwrite);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("创建输出目录失败 {:?}：{}", parent, e))?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars<'a>(book: &'a str, volume: &'a str, index: usize) -> OutputVars<'a> {
+        OutputVars {
+            book,
+            volume,
+            author: "作者",
+            source: "bilinovel",
+            id: "2356",
+            index,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let path = render("{book}/{number:02} {volume}.epub", &vars("书", "第一卷", 0)).unwrap();
+        assert_eq!(path, PathBuf::from("书/01 第一卷.epub"));
+        assert_eq!(
+            render(DEFAULT_TEMPLATE, &vars("书", "a/b:c?", 3)).unwrap(),
+            PathBuf::from("a_b_c_.epub")
+        );
+        assert_eq!(
+            render("{book}/{index:3}.epub", &vars("CON", "", 7)).unwrap(),
+            PathBuf::from("_CON/007.epub")
+        );
+        assert!(render("{title}.epub", &vars("书", "卷", 0)).is_err());
+        assert!(render("{book", &vars("书", "卷", 0)).is_err());
+
+        let long = "长".repeat(300);
+        let path = render("{volume}.epub", &vars("书", &long, 0)).unwrap();
+        let name = path.to_str().unwrap();
+        assert_eq!(name.chars().count(), MAX_COMPONENT_CHARS);
+        assert!(name.ends_with(".epub"));
+    }
+
+    #[test]
+    fn test_claim() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("书.epub");
+        // 没有写入成功的路径不占用
+        assert_eq!(available_path(&path, false), path);
+        assert_eq!(available_path(&path, false), path);
+        claim(&path);
+        let second = available_path(&path, true);
+        assert_eq!(second, dir.path().join("书 (2).epub"));
+        claim(&second);
+        assert_eq!(available_path(&path, true), dir.path().join("书 (3).epub"));
+
+        // 之前运行留下的文件
+        let old = dir.path().join("旧.epub");
+        std::fs::write(&old, "").unwrap();
+        assert_eq!(available_path(&old, false), dir.path().join("旧 (2).epub"));
+        assert_eq!(available_path(&old, true), old);
+    }
+}
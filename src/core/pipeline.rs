@@ -0,0 +1,999 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use crossterm::style::Stylize;
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, watch};
+use tracing::{error, info, warn};
+
+use crate::core::check::check_volume;
+use crate::core::concurrency::AdaptiveConcurrency;
+use crate::core::get_index::read_index_from_stdin;
+use crate::core::model::{Book, Volume};
+use crate::core::options::{ChapterSelection, DownloadOptions};
+#[cfg(feature = "epub")]
+use crate::core::output::{claim, output_path};
+use crate::core::output::{DEFAULT_TEMPLATE, OutputVars, render};
+use crate::core::report::VolumeReport;
+use crate::core::source::{Phase, Source};
+use crate::core::state::{StateHeader, StatePaths};
+#[cfg(feature = "epub")]
+use crate::utils::epub::EpubGenerator;
+#[cfg(feature = "epub")]
+use crate::utils::epub::validate::validate_epub;
+use crate::utils::fetch;
+use crate::utils::fetch::limit::{RateLimit, set_rate_limit};
+#[cfg(feature = "epub")]
+use crate::utils::httpserver::get_all_images;
+#[cfg(feature = "server")]
+use crate::utils::httpserver::start_server;
+use crate::utils::httpserver::{
+    AppConfig, ImageData, get_controller, get_image_by_url, init_controller,
+    load_images_from_file, save_images_to_file, update_config,
+};
+use crate::utils::input::{UserCommand, ask_yes_no, create_key_listener, interrupted};
+use crate::utils::progressbar::progress_monitor::ProgressMonitor;
+use crate::utils::terminal::clear_previous_line;
+
+/// 下载配置，从 `./config/{网站名}.json` 读取
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    /// 章节下载的最大并发数
+    pub max_concurrent: usize,
+    /// 最多检查几轮
+    pub check_rounds: usize,
+    /// epub使用的css文件
+    pub css: String,
+    /// 图片压缩等级
+    pub compression_level: u32,
+    /// 检查时的最大并发数
+    pub check_concurrent: usize,
+    /// 每下载多少章保存一次状态
+    pub save_interval: usize,
+    /// 每个主机的请求频率限制，所有下载方式共享
+    pub rate_limit: RateLimit,
+    /// 章节遇到反爬页面时的最多重试次数
+    pub block_retries: usize,
+    /// 遇到反爬页面后等待的秒数，每次重试递增
+    pub block_backoff_secs: u64,
+    /// epub的输出目录
+    pub output_dir: String,
+    /// epub的文件名模板，可以包含子目录，见 [`crate::core::output`]
+    pub output_template: String,
+    /// 覆盖输出目录中已有的同名文件，为 false 时在文件名后加上序号
+    pub overwrite: bool,
+    /// 打包后校验生成的epub
+    pub validate: bool,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 10,
+            check_rounds: 100,
+            css: "./assets/epub-style.css".to_string(),
+            compression_level: 6,
+            check_concurrent: 8,
+            save_interval: 20,
+            rate_limit: RateLimit::default(),
+            block_retries: 3,
+            block_backoff_secs: 10,
+            output_dir: "./output".to_string(),
+            output_template: DEFAULT_TEMPLATE.to_string(),
+            overwrite: false,
+            validate: true,
+        }
+    }
+}
+
+impl PipelineConfig {
+    /// 从文件读取配置
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let config = serde_json::from_str(&content)?;
+        Ok(config)
+    }
+}
+
+/// 通用的下载流程：调度章节下载、保存状态、检查和打包
+pub struct Pipeline {
+    source: Box<dyn Source>,
+    config: PipelineConfig,
+    book: Book,
+}
+
+impl Pipeline {
+    pub fn new(source: Box<dyn Source>) -> Self {
+        Self {
+            source,
+            config: PipelineConfig::default(),
+            book: Book::default(),
+        }
+    }
+
+    /// 检查下载所需的外部程序
+    pub fn check(&self) -> Result<()> {
+        self.source.check()
+    }
+
+    /// 书名，需要先调用 display
+    pub fn book_name(&self) -> &str {
+        &self.book.title
+    }
+
+    /// 书籍信息，需要先调用 display
+    pub fn book(&self) -> &Book {
+        &self.book
+    }
+
+    /// 获取并显示书籍信息和卷列表
+    pub async fn display(&mut self) -> Result<()> {
+        let mut book = self.source.fetch_book_info().await?;
+        book.volumes = self.source.list_volumes(&book).await?;
+        self.book = book;
+        self.source.display(&self.book);
+        println!("--------------------");
+        Ok(())
+    }
+
+    /// 下载、检查并打包，返回每一卷的处理结果
+    pub async fn download(&mut self, options: &DownloadOptions) -> Result<Vec<VolumeReport>> {
+        self.load_config();
+        let index = self.select_volumes(options);
+
+        //服务器的配置及启动
+        let appconfig = AppConfig::from_file("./config/http.json").unwrap_or_else(|e| {
+            error!("服务器配置读取失败，将使用默认值：{}", e);
+            eprintln!("服务器配置读取失败，将使用默认值：{}", e);
+            AppConfig::default()
+        });
+
+        // 批量下载时服务器已经启动，只需更新配置
+        if get_controller().is_ok() {
+            update_config(|f| *f = appconfig)?;
+        } else {
+            init_controller(appconfig)?;
+            #[cfg(feature = "server")]
+            start_server()?;
+        }
+
+        // 打包合集时每一卷只下载和检查，最后一起打包
+        let volume_options = if options.omnibus {
+            DownloadOptions {
+                pack: Some(false),
+                ..options.clone()
+            }
+        } else {
+            options.clone()
+        };
+
+        let mut reports = Vec::new();
+        let mut omnibus = Vec::new();
+        let mut omnibus_images = HashMap::new();
+        for (n, i) in index.into_iter().enumerate() {
+            let Some(name) = self.book.volumes.get(i).map(|v| v.name.clone()) else {
+                eprintln!("第{}卷不存在，跳过", i);
+                continue;
+            };
+            let volume_options = DownloadOptions {
+                series_index: options.series_index.map(|start| start + n as u32),
+                ..volume_options.clone()
+            };
+            let report = match self.download_volume(i, &volume_options).await {
+                Ok(report) => report,
+                Err(e) => {
+                    error!("{}处理出错：{}", name, e);
+                    eprintln!("{}处理出错：{}", name, e);
+                    VolumeReport::new(&name).with_error(e)
+                }
+            };
+            // 每一卷的图片只在处理这一卷时在图片存储中，打包合集前先收集起来
+            if options.omnibus && report.error.is_none() {
+                for image in self.book.volumes[i].images() {
+                    if let Ok(Some(data)) = get_image_by_url(&image.url) {
+                        omnibus_images.insert(image.url.clone(), data);
+                    }
+                }
+                omnibus.push(i);
+            }
+            reports.push(report);
+            // Ctrl+C 中止后不再开始下一卷
+            if interrupted() {
+                eprintln!("下载已中断，跳过剩下的卷");
+                break;
+            }
+        }
+
+        if !interrupted()
+            && !omnibus.is_empty()
+            && ask_yes_no("是否打包合集epub?(y-继续)", options.pack)?
+        {
+            let result = self.pack_omnibus(&omnibus, &omnibus_images, options);
+            for report in reports.iter_mut().filter(|r| r.error.is_none()) {
+                match &result {
+                    Ok(output) => report.output = output.clone(),
+                    Err(e) => report.error = Some(format!("打包合集出错：{}", e)),
+                }
+            }
+            if let Err(e) = result {
+                error!("打包合集出错：{}", e);
+                eprintln!("打包合集出错：{}", e);
+            }
+        }
+        Ok(reports)
+    }
+
+    /// 删除指定卷的下载数据
+    pub async fn clean(&mut self, options: &DownloadOptions) -> Result<()> {
+        self.load_config();
+        let index = self.select_volumes(options);
+        for i in index {
+            match self.book.volumes.get(i) {
+                Some(v) => {
+                    self.state_paths(v, options).remove();
+                    println!("已删除下载数据：{}", v.name.to_owned().dark_yellow());
+                }
+                None => eprintln!("第{}卷不存在，跳过", i),
+            }
+        }
+        Ok(())
+    }
+
+    ///加载并发数、检查轮数等配置
+    fn load_config(&mut self) {
+        let config_path = format!("./config/{}.json", self.source.name());
+        info!("开始从{}加载下载配置", config_path);
+        self.config = PipelineConfig::load(&config_path).unwrap_or_else(|e| {
+            error!("下载配置读取失败，将使用默认值：{}", e);
+            eprintln!("下载配置读取失败，将使用默认值：{}", e);
+            PipelineConfig::default()
+        });
+        set_rate_limit(self.config.rate_limit.clone());
+    }
+
+    ///要处理的卷的序号，没有指定时从终端读取
+    fn select_volumes(&self, options: &DownloadOptions) -> Vec<usize> {
+        let selection = match &options.volumes {
+            Some(selection) => selection.clone(),
+            None => read_index_from_stdin(),
+        };
+        selection.resolve(&self.book.volumes, |i| !self.is_packed(i))
+    }
+
+    ///设置卷的系列和在系列中的序号，没有指定时以书名为系列、卷的序号加一为序号
+    fn assign_series(&self, volume: &mut Volume, i: usize, options: &DownloadOptions) {
+        let metadata = &mut volume.metadata;
+        if let Some(series) = &options.series {
+            metadata.series = Some(series.clone());
+        }
+        if let Some(index) = options.series_index {
+            metadata.series_index = Some(index);
+        }
+        metadata.series.get_or_insert_with(|| self.book.title.clone());
+        metadata.series_index.get_or_insert(i as u32 + 1);
+    }
+
+    ///一卷的下载数据的路径，只处理部分章节时和整卷分开保存
+    fn state_paths(&self, volume: &Volume, options: &DownloadOptions) -> StatePaths {
+        match &options.chapters {
+            Some(chapters) => StatePaths::for_chapters(&self.book, volume, chapters),
+            None => StatePaths::for_volume(&self.book, volume),
+        }
+    }
+
+    ///卷是否已经打包，输出目录中有按模板生成的文件
+    fn is_packed(&self, i: usize) -> bool {
+        let Some(volume) = self.book.volumes.get(i) else {
+            return false;
+        };
+        render(
+            &self.config.output_template,
+            &OutputVars::new(&self.book, volume, i),
+        )
+        .is_ok_and(|path| Path::new(&self.config.output_dir).join(path).exists())
+    }
+
+    /// 显示指定卷的章节列表，指定了章节时用 * 标出选择的章节
+    pub async fn display_chapters(&mut self, options: &DownloadOptions) -> Result<()> {
+        self.load_config();
+        let index = self.select_volumes(options);
+        for i in index {
+            let Some(volume) = self.book.volumes.get(i) else {
+                eprintln!("第{}卷不存在，跳过", i);
+                continue;
+            };
+            let mut volume = volume.clone();
+            self.list_volume_chapters(&mut volume).await?;
+            let selected = options
+                .chapters
+                .as_ref()
+                .map(|chapters| chapters.resolve(&volume.chapters));
+            println!("[{}] {}", i, volume.name.to_owned().dark_yellow());
+            for (c, chapter) in volume.chapters.iter().enumerate() {
+                let mark = match &selected {
+                    Some(selected) if selected.contains(&c) => "*",
+                    _ => " ",
+                };
+                let kind = if chapter.is_illustration() {
+                    "（插图）"
+                } else if chapter.is_afterword() {
+                    "（后记）"
+                } else {
+                    ""
+                };
+                println!("{} {:>4}  {}{}", mark, c, chapter.title, kind);
+            }
+        }
+        Ok(())
+    }
+
+    ///获取卷的章节列表
+    async fn list_volume_chapters(&mut self, volume: &mut Volume) -> Result<()> {
+        self.source.begin(Phase::Download).await?;
+        let result = self.source.list_chapters(volume).await;
+        self.source.end(Phase::Download).await;
+        result
+    }
+
+    ///只保留卷中选择的章节，卷名后加上选择的章节
+    async fn select_chapters(&mut self, i: usize, chapters: &ChapterSelection) -> Result<()> {
+        let mut volume = self.book.volumes[i].clone();
+        if volume.chapters.is_empty() {
+            self.list_volume_chapters(&mut volume).await?;
+        }
+        let indices = chapters.resolve(&volume.chapters);
+        if indices.is_empty() {
+            return Err(anyhow!("{}中没有符合{}的章节", volume.name, chapters));
+        }
+        info!("{}选择了{}章", volume.name, indices.len());
+        volume.chapters = indices
+            .iter()
+            .map(|&c| volume.chapters[c].clone())
+            .collect();
+        volume.pending_chapter_indices.clear();
+        volume.name = format!("{}（{}）", volume.name, chapters);
+        self.book.volumes[i] = volume;
+        Ok(())
+    }
+
+    ///下载、检查并打包一卷
+    async fn download_volume(
+        &mut self,
+        i: usize,
+        options: &DownloadOptions,
+    ) -> Result<VolumeReport> {
+        let volume = &self.book.volumes[i];
+        let mut report = VolumeReport::new(&volume.name);
+        let mut paths = self.state_paths(volume, options);
+
+        let state = if options.download {
+            if let Some(chapters) = &options.chapters {
+                self.select_chapters(i, chapters).await?;
+            }
+            self.fetch_volume(i, &mut paths, options).await?
+        } else {
+            // 不下载时只能使用已保存的状态
+            if !paths.exists() {
+                return Err(anyhow!(
+                    "没有找到{}的下载数据：{}",
+                    volume.name,
+                    paths.state
+                ));
+            }
+            let saved = paths.load()?;
+            if let Err(e) = load_images_from_file(&paths.images) {
+                eprintln!("读取图片数据失败：{}", e);
+            }
+            Arc::new(Mutex::new(saved))
+        };
+
+        if ask_yes_no("是否开始检查章节?(y-继续)", options.check)? {
+            self.check_chapters(&state, &paths).await?;
+        }
+
+        let mut volume = state.lock().await.clone();
+        volume.inherit(&self.book);
+        self.assign_series(&mut volume, i, options);
+        report.failed_chapters = check_volume(&volume).failed_chapters();
+        self.book.volumes[i] = volume;
+
+        if ask_yes_no("是否开始打包epub?(y-继续)", options.pack)? {
+            report.output = self.pack_volume(i, &paths, options)?;
+        }
+        Ok(report)
+    }
+
+    ///下载一卷的章节，发现未完成的下载时询问是否恢复
+    async fn fetch_volume(
+        &mut self,
+        i: usize,
+        paths: &mut StatePaths,
+        options: &DownloadOptions,
+    ) -> Result<Arc<Mutex<Volume>>> {
+        let volume = &self.book.volumes[i];
+        let mut already = false;
+        // 检查是否有保存的状态
+        let state = if paths.exists() {
+            let saved = paths.load()?;
+
+            // 询问用户是否恢复下载
+            println!("发现未完成的下载:{}", volume.name.to_owned().dark_yellow());
+            println!(
+                "- 已完成章节: {}",
+                saved.chapters.len() - saved.pending_chapter_indices.len()
+            );
+            println!("- 待完成章节: {}", saved.pending_chapter_indices.len());
+
+            if ask_yes_no("是否恢复下载? (y-恢复, n-重新开始)", options.resume)? {
+                if saved.pending_chapter_indices.is_empty() && !saved.chapters.is_empty() {
+                    already = true;
+                }
+                if let Err(e) = load_images_from_file(&paths.images) {
+                    eprintln!("读取图片数据失败：{}", e);
+                }
+                saved
+            } else {
+                paths.header = StateHeader::new(&self.book.source, &self.book.id);
+                volume.clone()
+            }
+        } else {
+            volume.clone()
+        };
+        let name = state.name.clone();
+        let state = Arc::new(Mutex::new(state));
+
+        println!("\n");
+        if !already {
+            self.source.begin(Phase::Download).await?;
+            println!("开始下载[按q中止下载，p暂停，r继续，s查看状态]：{}\n", name.to_owned().dark_yellow());
+            let result = fetch_chapters(
+                self.source.as_ref(),
+                &state,
+                self.config.max_concurrent,
+                paths,
+                &self.config,
+            )
+            .await;
+            self.source.end(Phase::Download).await;
+            match result {
+                Ok(_) => info!("{}下载成功", name),
+                Err(e) => {
+                    error!("{}下载出错：{}", name, e);
+                    return Err(anyhow!("下载出错：{}", e));
+                }
+            }
+        }
+        Ok(state)
+    }
+
+    ///多轮检查一卷的章节，按q退出检查
+    async fn check_chapters(
+        &mut self,
+        state: &Arc<Mutex<Volume>>,
+        paths: &StatePaths,
+    ) -> Result<()> {
+        let max_echo = self.config.check_rounds;
+
+        // 创建按键监听器
+        let (stop_tx, mut cmd_rx) = create_key_listener();
+        // 创建取消标志
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancelled_clone = cancelled.clone();
+        // 启动一个任务来监听按键事件
+        let key_listener_handle = tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                if let UserCommand::Quit | UserCommand::Interrupt = cmd {
+                    println!("\n用户请求退出");
+                    cancelled_clone.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        });
+
+        if Path::new("./config/http_check.json").exists() {
+            let appconfig_check =
+                AppConfig::from_file("./config/http_check.json").unwrap_or_else(|e| {
+                    error!("从文件读取AppConfig配置失败，使用默认配置：{}", e);
+                    eprintln!("从文件读取AppConfig配置失败，使用默认配置：{}", e);
+                    AppConfig::default()
+                });
+            match update_config(|f| {
+                *f = appconfig_check;
+            }) {
+                Ok(_) => {
+                    info!("从{}读取配置成功", "./config/http_check.json");
+                }
+                Err(e) => {
+                    error!("从{}读取配置失败:{}", "./config/http_check.json", e);
+                }
+            }
+        }
+
+        let result = self.source.begin(Phase::Check).await;
+        if result.is_ok() {
+            for i in 1..=max_echo {
+                // 检查是否有按键命令，Ctrl+C 可能被重新下载章节时的监听器收到
+                if cancelled.load(Ordering::Relaxed) || interrupted() {
+                    println!("检查已被取消");
+                    break;
+                }
+
+                println!(
+                    "开始检查章节[没有问题将提前退出，按q退出检查]：第{}/{}轮检查……",
+                    i, max_echo
+                );
+                match self.check_round(state, paths).await {
+                    Ok(_) => {
+                        println!("检查章节完成！");
+                        break;
+                    }
+                    Err(e) => {
+                        clear_previous_line(1).unwrap_or_else(|e| eprintln!("清除屏幕失败：{}", e));
+                        eprintln!("检查出错 {}", e)
+                    }
+                }
+            }
+        }
+        self.source.end(Phase::Check).await;
+
+        // 停止按键监听器
+        let _ = stop_tx.send(()).await;
+        key_listener_handle.await?;
+        result
+    }
+
+    ///检查一轮，重新下载缺页或缺少图片的章节，有问题时返回错误
+    async fn check_round(&self, state: &Arc<Mutex<Volume>>, paths: &StatePaths) -> Result<()> {
+        info!("开始检查是否缺少图片或缺页");
+        let volume = state.lock().await.clone();
+        let result = check_volume(&volume);
+        let mut res = Ok(());
+
+        if result.missing_cover {
+            let cover = volume
+                .cover
+                .as_ref()
+                .map(|c| c.url.as_str())
+                .unwrap_or_default();
+            warn!("缺少封面图片。小说url：{}，图片url：{}", volume.url, cover);
+            warn!("开始重写下载小说url：{}，图片url：{}", volume.url, cover);
+            res = Err(anyhow!("缺少封面图片"));
+            // 重新获取卷的信息来下载封面
+            let mut fresh = Volume::new(&volume.url, &volume.name);
+            self.source.list_chapters(&mut fresh).await?;
+        }
+
+        for problem in &result.chapters {
+            if problem.missing_pages {
+                warn!("缺页。章节url：{}", problem.url);
+            }
+            for image in &problem.missing_images {
+                warn!("缺少图片。章节url：{}，图片url：{}", problem.url, image.url);
+
+                update_config(|f| {
+                    f.regex_pattern.push(image.url.to_owned());
+                })
+                .unwrap_or_else(|e| error!("更新正则表达式失败：{}", e));
+            }
+        }
+
+        if !result.chapters.is_empty() {
+            res = Err(anyhow!("存在章节缺少图片或缺页"));
+
+            //将有问题的页面重新加入待下载队列
+            state.lock().await.pending_chapter_indices = result.failed_indices();
+
+            match fetch_chapters(
+                self.source.as_ref(),
+                state,
+                self.config.check_concurrent,
+                paths,
+                &self.config,
+            )
+            .await
+            {
+                Ok(_) => {
+                    info!("重新下载成功");
+                }
+                Err(_) => {
+                    error!("重新下载失败");
+                }
+            }
+        }
+
+        res
+    }
+
+    ///将一卷打包为epub，成功后询问是否删除下载数据，返回生成的文件路径
+    #[cfg(feature = "epub")]
+    fn pack_volume(
+        &self,
+        i: usize,
+        paths: &StatePaths,
+        options: &DownloadOptions,
+    ) -> Result<Option<PathBuf>> {
+        let volume = &self.book.volumes[i];
+        println!("打包[{}]中...", volume.name.to_owned().dark_green());
+        let images = get_all_images().map_err(|e| {
+            error!("{}", e);
+            anyhow!("提取图片错误：{}", e)
+        })?;
+
+        let generator = EpubGenerator::new(volume, &images).with_book(&self.book);
+        let output = output_path(
+            &self.config.output_dir,
+            &self.config.output_template,
+            &OutputVars::new(&self.book, volume, i),
+            self.config.overwrite,
+        )?;
+        let file = std::fs::File::create(&output).map_err(|e| {
+            error!("{}", e);
+            anyhow!("创建文件失败 {:?}：{}", output, e)
+        })?;
+        generator
+            .with_css(&self.config.css)
+            .generate_epub(file)
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&output);
+                anyhow!("打包章节出错：{}", e)
+            })?;
+        claim(&output);
+        self.validate_output(&output)?;
+        if ask_yes_no("生成成功！是否删除下载数据(y-删除)", options.clean)? {
+            paths.remove();
+        }
+        Ok(Some(output))
+    }
+
+    ///将多卷打包为一本合集，成功后询问是否删除这些卷的下载数据，返回生成的文件路径
+    #[cfg(feature = "epub")]
+    fn pack_omnibus(
+        &self,
+        index: &[usize],
+        images: &HashMap<String, ImageData>,
+        options: &DownloadOptions,
+    ) -> Result<Option<PathBuf>> {
+        let volumes: Vec<&Volume> = index.iter().map(|&i| &self.book.volumes[i]).collect();
+        // 包含所有卷时使用书名，否则在书名后加上起止的卷
+        let title = match (volumes.first(), volumes.last()) {
+            (Some(first), Some(last)) if volumes.len() < self.book.volumes.len() => {
+                format!("{}（{}～{}）", self.book.title, first.name, last.name)
+            }
+            _ => self.book.title.clone(),
+        };
+        println!("打包合集[{}]中...", title.to_owned().dark_green());
+
+        let generator =
+            EpubGenerator::omnibus(&title, volumes.clone(), images).with_book(&self.book);
+        let output = output_path(
+            &self.config.output_dir,
+            &self.config.output_template,
+            &OutputVars {
+                volume: &title,
+                ..OutputVars::new(&self.book, volumes[0], index[0])
+            },
+            self.config.overwrite,
+        )?;
+        let file = std::fs::File::create(&output).map_err(|e| {
+            error!("{}", e);
+            anyhow!("创建文件失败 {:?}：{}", output, e)
+        })?;
+        generator
+            .with_css(&self.config.css)
+            .generate_epub(file)
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&output);
+                anyhow!("打包章节出错：{}", e)
+            })?;
+        claim(&output);
+        self.validate_output(&output)?;
+        if ask_yes_no("生成成功！是否删除下载数据(y-删除)", options.clean)? {
+            for volume in volumes {
+                self.state_paths(volume, options).remove();
+            }
+        }
+        Ok(Some(output))
+    }
+
+    ///校验生成的epub，有错误时保留下载数据并返回错误
+    #[cfg(feature = "epub")]
+    fn validate_output(&self, output: &Path) -> Result<()> {
+        if !self.config.validate {
+            return Ok(());
+        }
+        let report = validate_epub(output)?;
+        if report.issues.is_empty() {
+            return Ok(());
+        }
+        if report.is_ok() {
+            warn!("{}", report);
+        } else {
+            error!("{}", report);
+        }
+        eprintln!("校验 {}", report);
+        if !report.is_ok() {
+            return Err(anyhow!(
+                "生成的epub没有通过校验，下载数据已保留：{}",
+                output.display()
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "epub"))]
+    fn pack_omnibus(
+        &self,
+        _index: &[usize],
+        _images: &HashMap<String, ImageData>,
+        _options: &DownloadOptions,
+    ) -> Result<Option<PathBuf>> {
+        Err(anyhow!("未启用 epub 功能，无法打包"))
+    }
+
+    #[cfg(not(feature = "epub"))]
+    fn pack_volume(
+        &self,
+        _i: usize,
+        _paths: &StatePaths,
+        _options: &DownloadOptions,
+    ) -> Result<Option<PathBuf>> {
+        Err(anyhow!("未启用 epub 功能，无法打包"))
+    }
+}
+
+///并发下载待下载队列中的章节，定期保存状态，按q中止，p暂停，r继续，s查看状态
+async fn fetch_chapters(
+    source: &dyn Source,
+    state: &Arc<Mutex<Volume>>,
+    max_concurrent: usize,
+    paths: &StatePaths,
+    config: &PipelineConfig,
+) -> Result<()> {
+    let (name, chapters, pending) = {
+        let mut volume = state.lock().await;
+        //不为空说明是恢复的下载
+        if volume.chapters.is_empty() {
+            info!("chapter为空");
+            source.list_chapters(&mut volume).await?;
+        }
+        if volume.chapters.is_empty() {
+            return Err(anyhow!("chapter为空"));
+        }
+        //为空说明是新建的下载，恢复的下载但是pending_chapter_indices为空的情况已经排除
+        if volume.pending_chapter_indices.is_empty() {
+            info!("新建的下载");
+            volume.pending_chapter_indices = (0..volume.chapters.len()).collect();
+        }
+        (
+            volume.name.clone(),
+            volume.chapters.clone(),
+            volume.pending_chapter_indices.clone(),
+        )
+    };
+
+    // 获取待下载章节数量
+    let pending_count = pending.len();
+    // 创建进度监控器
+    let progress = ProgressMonitor::new(pending_count, &name);
+    //返回较小的那一个
+    let max_concurrent = chapters.len().min(max_concurrent).max(1);
+
+    info!("chapter数量：{}", chapters.len());
+    info!("待下载数量：{}", pending_count);
+
+    let completed_count = Arc::new(AtomicUsize::new(0));
+
+    // 并发数在遇到拦截或错误时减半，连续成功后恢复，最多为 max_concurrent
+    let concurrency = Arc::new(AdaptiveConcurrency::new(max_concurrent));
+    progress.set_concurrency(concurrency.limit());
+
+    // 创建按键监听器
+    let (stop_tx, mut cmd_rx) = create_key_listener();
+    // 创建取消标志
+    let cancelled = Arc::new(AtomicBool::new(false));
+    // 中止正在进行的下载
+    let (abort_tx, abort_rx) = watch::channel(false);
+    // 启动一个任务来监听按键事件
+    let key_listener_handle = {
+        let cancelled = cancelled.clone();
+        let concurrency = concurrency.clone();
+        let progress = progress.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                match cmd {
+                    UserCommand::Quit | UserCommand::Interrupt => {
+                        progress.println("\n用户请求退出");
+                        cancelled.store(true, Ordering::Relaxed);
+                        // Ctrl+C 时不等待正在进行的下载，single-file进程随之结束
+                        if let UserCommand::Interrupt = cmd {
+                            let _ = abort_tx.send(true);
+                        }
+                        // 暂停中等待的章节需要继续才能结束
+                        concurrency.set_paused(false);
+                        break;
+                    }
+                    UserCommand::Pause => {
+                        concurrency.set_paused(true);
+                        progress.set_paused(true);
+                        progress.println("已暂停，正在下载的章节完成后不再开始新的章节，按 r 继续");
+                    }
+                    UserCommand::Resume => {
+                        concurrency.set_paused(false);
+                        progress.set_paused(false);
+                        progress.println("继续下载");
+                    }
+                    UserCommand::Status => {
+                        let volume = state.lock().await;
+                        progress.println(&status_panel(&volume, &progress, &concurrency));
+                    }
+                    UserCommand::Other(_) => {}
+                }
+            }
+        })
+    };
+
+    let chapter_futures = pending.into_iter().filter_map(|i| {
+        let chapter = chapters.get(i)?.clone();
+        let progress = progress.clone();
+        let completed_count = Arc::clone(&completed_count);
+        let cancelled = cancelled.clone();
+        let concurrency = concurrency.clone();
+        let mut aborted = abort_rx.clone();
+        Some(async move {
+            let mut attempt = 0;
+            let result = loop {
+                // 暂停时在这里等待，继续或退出后再检查是否取消
+                let permit = concurrency.acquire().await;
+                if cancelled.load(Ordering::Relaxed) {
+                    return (i, Err(anyhow!("任务被取消")));
+                }
+                info!("下载第{}个章节中", i);
+
+                let result = tokio::select! {
+                    result = source.fetch_chapter(&chapter) => result,
+                    Ok(_) = aborted.wait_for(|a| *a) => return (i, Err(anyhow!("任务被取消"))),
+                };
+                drop(permit);
+                match &result {
+                    Ok(_) => progress.set_concurrency(concurrency.on_success()),
+                    Err(_) => progress.set_concurrency(concurrency.on_failure()),
+                }
+                match result {
+                    Err(e) if fetch::is_blocked(&e) => {
+                        progress.record_block();
+                        if attempt >= config.block_retries {
+                            break Err(e);
+                        }
+                        attempt += 1;
+                        let backoff = config.block_backoff_secs * attempt as u64;
+                        warn!(
+                            "章节被拦截，{}秒后第{}次重试：{} - {}",
+                            backoff, attempt, chapter.title, e
+                        );
+                        tokio::select! {
+                            _ = tokio::time::sleep(Duration::from_secs(backoff)) => {}
+                            Ok(_) = aborted.wait_for(|a| *a) => return (i, Err(anyhow!("任务被取消"))),
+                        }
+                    }
+                    result => break result,
+                }
+            };
+            match result {
+                Ok(mut new_chapter) => {
+                    info!("章节下载成功：{}", chapter.title);
+                    new_chapter.images = source.images_for(&new_chapter);
+                    progress.increment();
+
+                    let mut volume = state.lock().await;
+                    // 更新章节内容
+                    if let Some(existing_chapter) = volume.chapters.get_mut(i) {
+                        *existing_chapter = new_chapter;
+                    }
+                    // 从待下载列表中移除
+                    volume.pending_chapter_indices.retain(|&idx| idx != i);
+
+                    let current_count = completed_count.fetch_add(1, Ordering::SeqCst) + 1;
+                    // 定期保存，全部结束后会再保存一次
+                    if current_count.is_multiple_of(config.save_interval.max(1)) {
+                        save_progress(&volume, paths, config.compression_level);
+                    }
+                    (i, Ok(()))
+                }
+                Err(e) => {
+                    progress.record_error();
+                    error!("章节下载失败: {} - {}", chapter.title, e);
+                    (i, Err(e))
+                }
+            }
+        })
+    });
+
+    // 等待所有任务完成
+    let results: Vec<(usize, Result<()>)> = stream::iter(chapter_futures)
+        .buffer_unordered(max_concurrent)
+        .collect()
+        .await;
+    info!("所有任务下载完成");
+    // 完成进度监控
+    progress.finish();
+
+    // 有章节失败时完成数达不到待下载数，最后总是保存一次
+    if cancelled.load(Ordering::Relaxed) {
+        println!("下载被取消，正在保存数据...");
+    }
+    save_progress(&*state.lock().await, paths, config.compression_level);
+
+    // 停止按键监听器
+    let _ = stop_tx.send(()).await;
+    key_listener_handle.await?;
+
+    // 检查错误
+    for (_, result) in results {
+        result?;
+    }
+    Ok(())
+}
+
+///按 s 时显示的下载状态
+fn status_panel(
+    volume: &Volume,
+    progress: &ProgressMonitor,
+    concurrency: &AdaptiveConcurrency,
+) -> String {
+    let failed = progress.error_count();
+    let completed = progress.completed_count().saturating_sub(failed);
+    let images: Vec<_> = volume
+        .chapters
+        .iter()
+        .flat_map(|c| c.images.iter())
+        .chain(volume.cover.iter())
+        .collect();
+    let collected = images
+        .iter()
+        .filter(|image| matches!(get_image_by_url(&image.url), Ok(Some(_))))
+        .count();
+    let eta = if completed == 0 {
+        "未知".to_string()
+    } else {
+        let secs = progress.pb.eta().as_secs();
+        format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    };
+    let state = if concurrency.is_paused() {
+        "已暂停"
+    } else {
+        "下载中"
+    };
+    format!(
+        "\n==== {} ({}) ====\n已完成：{}  待下载：{}  失败：{}\n图片：{}/{}\n并发：{}/{}  拦截：{} 次\n预计剩余时间：{}",
+        volume.name,
+        state,
+        completed,
+        progress.pending_count(),
+        failed,
+        collected,
+        images.len(),
+        concurrency.limit(),
+        concurrency.max(),
+        progress.block_count(),
+        eta
+    )
+}
+
+///保存下载状态和图片
+fn save_progress(volume: &Volume, paths: &StatePaths, compression_level: u32) {
+    match paths.save(volume) {
+        Ok(_) => info!("成功保存状态文件"),
+        Err(e) => error!("保存状态文件失败：{}", e),
+    }
+    match save_images_to_file(&paths.images, &paths.header, compression_level) {
+        Ok(_) => info!("成功保存图片"),
+        Err(e) => error!("保存图片失败：{}", e),
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use colored::Colorize;
+
+/// 一卷的处理结果
+#[derive(Debug, Clone, Default)]
+pub struct VolumeReport {
+    pub name: String,
+    /// 缺页或缺少图片的章节标题
+    pub failed_chapters: Vec<String>,
+    /// 生成的epub路径
+    pub output: Option<PathBuf>,
+    /// 处理过程中的错误
+    pub error: Option<String>,
+}
+
+impl VolumeReport {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..VolumeReport::default()
+        }
+    }
+
+    /// 记录错误
+    pub fn with_error(mut self, error: impl fmt::Display) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+
+    /// 没有错误且没有失败的章节
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.failed_chapters.is_empty()
+    }
+}
+
+/// 一本书的处理结果
+#[derive(Debug, Clone, Default)]
+pub struct BookReport {
+    pub url: String,
+    pub book_name: String,
+    pub volumes: Vec<VolumeReport>,
+    /// 解析书籍时的错误，此时没有任何卷的结果
+    pub error: Option<String>,
+}
+
+impl BookReport {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            ..BookReport::default()
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.volumes.iter().all(|v| v.is_success())
+    }
+}
+
+/// 打印批量下载的汇总表
+pub fn print_summary(reports: &[BookReport]) {
+    println!("\n{}", "==================== 汇总 ====================".bold());
+    println!("结果\t失败章节\t书籍/卷\t输出");
+    for book in reports {
+        let name = if book.book_name.is_empty() {
+            &book.url
+        } else {
+            &book.book_name
+        };
+        if let Some(e) = &book.error {
+            println!("{}\t-\t{}\t{}", "失败".red(), name, e);
+            continue;
+        }
+        if book.volumes.is_empty() {
+            println!("{}\t-\t{}\t没有处理任何卷", "跳过".yellow(), name);
+        }
+        for v in &book.volumes {
+            let state = if v.is_success() {
+                "成功".green()
+            } else {
+                "失败".red()
+            };
+            let output = match (&v.error, &v.output) {
+                (Some(e), _) => e.clone(),
+                (None, Some(path)) => path.display().to_string(),
+                (None, None) => "未打包".to_string(),
+            };
+            println!(
+                "{}\t{}\t{} / {}\t{}",
+                state,
+                v.failed_chapters.len(),
+                name,
+                v.name,
+                output
+            );
+            for title in &v.failed_chapters {
+                println!("\t  - {}", title);
+            }
+        }
+    }
+    let success = reports.iter().filter(|r| r.is_success()).count();
+    println!("成功 {}/{} 本", success, reports.len());
+}
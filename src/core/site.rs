@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use url::Url;
+
+use crate::core::init::UrlPattern;
+use crate::utils::fetch::{self, Blocked, FetchBackend, Fetcher};
+use crate::utils::httpclient::http_async::AsyncHttpClient;
+use crate::utils::httpclient::types::RequestConfig;
+
+/// 内置的哔哩轻小说定义，`./config/sites/bilinovel.json` 不存在时使用
+const BILINOVEL_DEFINITION: &str = include_str!("../../config/sites/bilinovel.json");
+
+/// 网站定义，保存在 `./config/sites/*.json`，修改网站布局或添加简单的网站不需要重新编译
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SiteDefinition {
+    /// 来源标识，为定义文件名（不含扩展名），下载配置从 `./config/{key}.json` 读取
+    #[serde(skip)]
+    pub key: String,
+    /// 网站名，显示在输入提示中
+    pub name: String,
+    /// 解析方式：`generic` 为通用解析，`bilinovel` 使用哔哩轻小说的解析
+    #[serde(default = "default_parser")]
+    pub parser: String,
+    /// 链接规则
+    pub url: UrlPattern,
+    /// 目录页链接，`{id}` 会被替换为书籍号，书籍页没有卷列表时使用
+    #[serde(default)]
+    pub catalog: Option<String>,
+    /// 章节的下载方式，bilinovel 解析时有效
+    #[serde(default)]
+    pub backend: ChapterBackend,
+    /// 出现这些文本时认为是反爬页面，http下载会改用single-file
+    #[serde(default)]
+    pub block_markers: Vec<String>,
+    /// http请求附加的请求头，例如图片需要的 `Referer`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub selectors: SiteSelectors,
+}
+
+/// 章节的下载方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterBackend {
+    /// 通过浏览器和single-file下载
+    #[default]
+    SingleFile,
+    /// 直接通过http下载，不需要浏览器
+    Http,
+}
+
+/// 页面元素的css选择器
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteSelectors {
+    /// 书名
+    pub title: String,
+    /// 作者
+    pub author: String,
+    /// 简介，bilinovel 解析时为书籍信息的容器
+    pub description: String,
+    /// 书籍信息容器中的统计数据，bilinovel 解析时有效，为空时不提取
+    pub stats: String,
+    /// 书籍信息容器中的简介，bilinovel 解析时有效，为空时不提取
+    pub intro: String,
+    /// 书籍信息容器中的公告，bilinovel 解析时有效，为空时不提取
+    pub notice: String,
+    /// 标签，bilinovel 解析时为标签的容器
+    pub tags: String,
+    /// 标签容器中的连载状态，bilinovel 解析时有效，为空时不提取
+    pub state: String,
+    /// 标签容器中的分类，bilinovel 解析时有效，为空时不提取
+    pub label: String,
+    /// 标签容器中的其他标签，例如文库，bilinovel 解析时有效，为空时不提取
+    pub tag_link: String,
+    /// 插画作者，为空时不提取
+    pub illustrator: String,
+    /// 出版社或文库，为空时不提取，bilinovel 使用标签中的文库
+    pub publisher: String,
+    /// 封面图片
+    pub cover: String,
+    /// 封面图片链接所在的属性
+    pub cover_attr: String,
+    /// 卷列表的容器，为空时整本书作为一卷
+    pub volumes: String,
+    /// 卷列表容器中卷的链接
+    pub volume_link: String,
+    /// 卷名所在的属性，为空时取链接的文本
+    pub volume_name_attr: String,
+    /// 目录页中卷列表的容器
+    pub catalog_volumes: String,
+    /// 目录页卷列表容器中的卷
+    pub catalog_volume: String,
+    /// 目录页卷中的链接
+    pub catalog_volume_link: String,
+    /// 目录页卷中的卷名
+    pub catalog_volume_name: String,
+    /// 章节列表的容器
+    pub chapters: String,
+    /// 章节列表容器中章节的链接
+    pub chapter_link: String,
+    /// 正文
+    pub content: String,
+    /// 正文中要删除的元素
+    pub remove: Vec<String>,
+    /// 正文图片链接所在的属性
+    pub image_attr: String,
+    /// http下载时封面链接所在的属性，single-file保存的页面和原始页面的属性可能不同
+    pub http_cover_attr: String,
+    /// http下载时正文图片链接所在的属性
+    pub http_image_attr: String,
+}
+
+fn default_parser() -> String {
+    "generic".to_string()
+}
+
+impl SiteDefinition {
+    /// 从文件读取网站定义
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("读取网站定义失败：{:?}", path))?;
+        let mut site =
+            Self::from_json(&content).with_context(|| format!("解析网站定义失败：{:?}", path))?;
+        site.key = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+        Ok(site)
+    }
+
+    pub fn from_json(content: &str) -> Result<Self> {
+        let mut site: SiteDefinition = serde_json::from_str(content)?;
+        site.selectors.fill_defaults();
+        Ok(site)
+    }
+
+    /// 内置的哔哩轻小说定义
+    pub fn bilinovel() -> Self {
+        let mut site = Self::from_json(BILINOVEL_DEFINITION).expect("内置的网站定义无效");
+        site.key = "bilinovel".to_string();
+        site
+    }
+
+    /// 从书籍链接提取书籍号，规则与注册表相同
+    pub fn book_id(&self, url: &str) -> Option<String> {
+        let path = Url::parse(url).ok()?.path().to_string();
+        let captures = Regex::new(&self.url.path).ok()?.captures(&path)?;
+        let id = captures.name("id").or_else(|| captures.get(1))?;
+        Some(id.as_str().to_string())
+    }
+
+    /// 页面是否为空或者是反爬页面
+    pub fn is_blocked(&self, html: &str) -> bool {
+        html.trim().is_empty() || self.block_markers.iter().any(|m| html.contains(m.as_str()))
+    }
+
+    /// 获取页面内容，状态码异常时返回错误，遇到反爬页面时返回 [`Blocked`]
+    pub async fn fetch_html(&self, fetcher: &dyn FetchBackend, url: &str) -> Result<String> {
+        let response = fetcher.fetch_page(url).await.map_err(|e| {
+            error!("发送请求失败：{}", e);
+            anyhow!("发送请求失败：{}", e)
+        })?;
+        if response.is_throttled() || (response.is_success() && self.is_blocked(&response.body)) {
+            error!("被反爬页面拦截：{}\tstatus:{}", url, response.status);
+            return Err(Blocked {
+                url: url.to_string(),
+            }
+            .into());
+        }
+        if !response.is_success() {
+            error!(
+                "获取失败: url:{},\tstatus:{}",
+                response.url, response.status
+            );
+            return Err(anyhow!(
+                "获取失败: url:{},\tstatus:{}",
+                response.url,
+                response.status
+            ));
+        }
+        Ok(response.body)
+    }
+
+    /// 按定义中的请求头创建http下载后端，录制或回放模式下会被包装
+    pub fn fetcher(&self) -> Result<Fetcher> {
+        let mut client = AsyncHttpClient::new(RequestConfig::default())
+            .with_context(|| format!("创建{}的http客户端失败", self.name))?;
+        for (name, value) in &self.headers {
+            client.set_header(name, value);
+        }
+        Ok(self.wrap(client))
+    }
+
+    /// 按运行模式包装下载后端，录制时不保存这个网站的反爬页面
+    pub fn wrap<B: FetchBackend + 'static>(&self, backend: B) -> Fetcher {
+        let site = self.clone();
+        fetch::wrap_checked(backend, Some(Arc::new(move |html| site.is_blocked(html))))
+    }
+
+    /// 网站的根链接，取自书籍链接模板，用于补全相对链接
+    pub fn base_url(&self) -> String {
+        Url::parse(&self.url.canonical)
+            .and_then(|u| u.join("/"))
+            .map(|u| u.to_string())
+            .unwrap_or_default()
+    }
+
+    /// 书籍号对应的目录页链接
+    pub fn catalog_url(&self, id: &str) -> Option<String> {
+        self.catalog.as_ref().map(|c| c.replace("{id}", id))
+    }
+}
+
+impl SiteSelectors {
+    /// 链接和属性的默认值
+    fn fill_defaults(&mut self) {
+        for (field, default) in [
+            (&mut self.cover_attr, "src"),
+            (&mut self.volume_link, "a"),
+            (&mut self.chapter_link, "a"),
+            (&mut self.image_attr, "src"),
+            (&mut self.http_cover_attr, "src"),
+            (&mut self.http_image_attr, "src"),
+        ] {
+            if field.is_empty() {
+                *field = default.to_string();
+            }
+        }
+    }
+}
+
+/// 读取目录下的所有网站定义，无效的定义会被跳过
+pub fn load_site_definitions<P: AsRef<Path>>(dir: P) -> Vec<SiteDefinition> {
+    let dir = dir.as_ref();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        info!("没有找到网站定义目录：{:?}", dir);
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let mut sites = Vec::new();
+    for path in paths {
+        match SiteDefinition::load(&path) {
+            Ok(site) => sites.push(site),
+            Err(e) => {
+                error!("{:#}", e);
+                eprintln!("{:#}", e);
+            }
+        }
+    }
+    sites
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_bilinovel() {
+        let site = SiteDefinition::bilinovel();
+        assert_eq!(site.parser, "bilinovel");
+        assert_eq!(
+            site.book_id("https://www.linovelib.com/novel/2356.html")
+                .as_deref(),
+            Some("2356")
+        );
+        assert_eq!(site.selectors.content, "div#TextContent");
+        assert_eq!(site.backend, ChapterBackend::Http);
+        assert!(site.is_blocked("<title>Just a moment...</title>"));
+        assert!(!site.is_blocked("<div id=\"TextContent\"></div>"));
+        assert_eq!(site.selectors.image_attr, "data-original-src");
+        assert_eq!(
+            site.catalog_url("2356").as_deref(),
+            Some("https://www.linovelib.com/novel/2356/catalog")
+        );
+        assert_eq!(site.base_url(), "https://www.linovelib.com/");
+    }
+
+    #[test]
+    fn test_defaults() {
+        let site = SiteDefinition::from_json(
+            r#"{
+                "name": "示例",
+                "url": {"hosts": ["example.com"], "path": "^/book/(\\d+)", "canonical": "https://example.com/book/{id}"},
+                "selectors": {"title": "h1", "chapters": "ul.chapters", "content": "div.content"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(site.parser, "generic");
+        assert_eq!(site.backend, ChapterBackend::SingleFile);
+        assert_eq!(site.selectors.chapter_link, "a");
+        assert_eq!(site.selectors.image_attr, "src");
+        assert!(site.catalog.is_none());
+    }
+}
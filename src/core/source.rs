@@ -0,0 +1,58 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::core::model::{Book, Chapter, ImageRef, Volume};
+
+/// 下载流程的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// 下载章节
+    Download,
+    /// 检查并重新下载缺页和缺少图片的章节
+    Check,
+}
+
+/// 小说网站
+///
+/// 每个网站只需要实现获取书籍信息、卷、章节列表和章节内容，
+/// 调度、状态保存、检查和打包由 [`crate::core::pipeline::Pipeline`] 完成
+#[async_trait]
+pub trait Source: Send + Sync {
+    /// 网站名，同时用于读取 `./config/{name}.json` 中的下载配置
+    fn name(&self) -> &str;
+
+    /// 检查下载所需的外部程序
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 获取书名、作者等书籍信息
+    async fn fetch_book_info(&mut self) -> Result<Book>;
+
+    /// 获取书籍的卷列表，卷中的章节可以为空
+    async fn list_volumes(&mut self, book: &Book) -> Result<Vec<Volume>>;
+
+    /// 获取卷中的章节列表，同时补全封面等卷的信息
+    async fn list_chapters(&self, volume: &mut Volume) -> Result<()>;
+
+    /// 下载章节内容，返回填好页面和图片的章节，会被并发调用
+    async fn fetch_chapter(&self, chapter: &Chapter) -> Result<Chapter>;
+
+    /// 章节需要的图片，下载完成后用于检查和打包，默认为解析出的所有图片
+    fn images_for(&self, chapter: &Chapter) -> Vec<ImageRef> {
+        chapter.images.clone()
+    }
+
+    /// 进入某个阶段前调用，用于启动浏览器等
+    async fn begin(&mut self, _phase: Phase) -> Result<()> {
+        Ok(())
+    }
+
+    /// 某个阶段结束后调用，无论成功与否
+    async fn end(&mut self, _phase: Phase) {}
+
+    /// 在终端显示书籍信息
+    fn display(&self, book: &Book) {
+        println!("{}", book);
+    }
+}
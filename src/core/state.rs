@@ -0,0 +1,480 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::{error, info, warn};
+
+use crate::core::model::{Book, Volume};
+use crate::core::options::ChapterSelection;
+use crate::utils::snapshot;
+
+/// 状态文件的格式版本，没有文件头的旧状态文件为版本1
+pub const STATE_FORMAT: u32 = 2;
+
+/// 状态文件的文件头
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateHeader {
+    /// 格式版本
+    pub format: u32,
+    /// 保存时的程序版本
+    pub tool_version: String,
+    /// 来源网站
+    pub source: String,
+    /// 书籍在来源网站的编号
+    pub book_id: String,
+    /// 开始下载的时间
+    pub created: String,
+}
+
+impl StateHeader {
+    pub fn new(source: &str, book_id: &str) -> Self {
+        Self {
+            format: STATE_FORMAT,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            source: source.to_string(),
+            book_id: book_id.to_string(),
+            created: Local::now().to_rfc3339(),
+        }
+    }
+}
+
+/// 状态文件的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateFile {
+    pub header: StateHeader,
+    pub volume: Volume,
+}
+
+/// 下载状态所在的目录
+pub const DOWNLOAD_DIR: &str = "./temp/download";
+/// 图片文件所在的目录
+pub const IMAGES_DIR: &str = "./temp/images";
+
+/// 一卷的下载数据的路径，按 `来源/书籍号/卷号` 保存，卷名保存在状态文件中
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatePaths {
+    /// 下载状态文件
+    pub state: String,
+    /// 图片文件
+    pub images: String,
+    /// 保存状态时写入的文件头
+    pub header: StateHeader,
+}
+
+impl StatePaths {
+    /// 一卷的下载数据的路径，旧的按卷名保存的下载数据会被移动到新的位置
+    pub fn for_volume(book: &Book, volume: &Volume) -> Self {
+        let paths = Self::in_dirs(DOWNLOAD_DIR, IMAGES_DIR, book, volume);
+        paths.adopt_legacy(DOWNLOAD_DIR, IMAGES_DIR, volume);
+        paths
+    }
+
+    /// 只处理部分章节时的下载数据的路径，和整卷的下载数据分开保存
+    pub fn for_chapters(book: &Book, volume: &Volume, chapters: &ChapterSelection) -> Self {
+        let mut paths = Self::in_dirs(DOWNLOAD_DIR, IMAGES_DIR, book, volume);
+        let suffix = format!(".chapters-{:016x}", fnv1a(&chapters.to_string()));
+        paths.state = paths
+            .state
+            .replace(".state.json", &format!("{}.state.json", suffix));
+        paths.images.push_str(&suffix);
+        paths
+    }
+
+    fn in_dirs(download_dir: &str, images_dir: &str, book: &Book, volume: &Volume) -> Self {
+        let key = format!(
+            "{}/{}/{}",
+            sanitize(&book.source),
+            sanitize(&book.id),
+            volume_key(volume)
+        );
+        Self {
+            state: format!("{}/{}.state.json", download_dir, key),
+            images: format!("{}/{}", images_dir, key),
+            header: StateHeader::new(&book.source, &book.id),
+        }
+    }
+
+    /// 移动以前按卷名保存的下载数据，只移动属于这一卷的数据
+    fn adopt_legacy(&self, download_dir: &str, images_dir: &str, volume: &Volume) {
+        let legacy_state = format!("{}/{}.state.json", download_dir, volume.name);
+        if self.exists() || !snapshot::exists(&legacy_state) {
+            return;
+        }
+        let belongs = match load_state(&legacy_state) {
+            Ok(saved) if saved.header.source.is_empty() => saved.volume.url == volume.url,
+            Ok(saved) => {
+                saved.header.source == self.header.source
+                    && saved.header.book_id == self.header.book_id
+            }
+            Err(_) => false,
+        };
+        if !belongs {
+            return;
+        }
+        let legacy_images = format!("{}/{}", images_dir, volume.name);
+        let result = snapshot::rename(&legacy_state, &self.state)
+            .and_then(|_| snapshot::rename(&legacy_images, &self.images));
+        match result {
+            Ok(_) => info!("移动旧的下载数据：{} -> {}", legacy_state, self.state),
+            Err(e) => {
+                error!("移动旧的下载数据失败：{}", e);
+                eprintln!("移动旧的下载数据失败：{}", e);
+            }
+        }
+    }
+
+    /// 是否有保存的下载状态
+    pub fn exists(&self) -> bool {
+        snapshot::exists(&self.state)
+    }
+
+    /// 删除一卷的下载数据
+    pub fn remove(&self) {
+        snapshot::remove(&self.state);
+        snapshot::remove(&self.images);
+    }
+
+    /// 读取保存的下载状态，之后保存时沿用原来的开始时间
+    pub fn load(&mut self) -> Result<Volume> {
+        let saved = load_state(&self.state)?;
+        let header = &saved.header;
+        // 迁移的旧状态文件中没有来源和书籍号
+        if !header.source.is_empty()
+            && (header.source != self.header.source || header.book_id != self.header.book_id)
+        {
+            warn!(
+                "状态文件属于{}的{}，当前为{}的{}：{}",
+                header.source, header.book_id, self.header.source, self.header.book_id, self.state
+            );
+        }
+        if !header.created.is_empty() {
+            self.header.created = header.created.clone();
+        }
+        Ok(saved.volume)
+    }
+
+    /// 保存下载状态
+    pub fn save(&self, volume: &Volume) -> Result<()> {
+        save_state(&self.state, &self.header, volume)
+    }
+}
+
+/// 卷在路径中的标识：卷号，没有卷号时为卷链接的哈希
+fn volume_key(volume: &Volume) -> String {
+    if !volume.id.is_empty() {
+        return sanitize(&volume.id);
+    }
+    format!("{:016x}", fnv1a(&volume.url))
+}
+
+/// FNV-1a哈希，保证不同版本的程序得到相同的结果
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// 只保留字母、数字、`-` 和 `_`，其他字符替换为 `_`
+fn sanitize(s: &str) -> String {
+    if s.is_empty() {
+        return "_".to_string();
+    }
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// 保存的下载状态的概要
+#[derive(Debug, Clone)]
+pub struct SavedState {
+    pub path: PathBuf,
+    pub header: StateHeader,
+    /// 卷名
+    pub name: String,
+    pub url: String,
+    /// 已完成的章节数
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// 列出目录下所有可以恢复的下载状态，按路径排序，无法读取的文件会被跳过
+pub fn list_states<P: AsRef<Path>>(dir: P) -> Vec<SavedState> {
+    let mut files = Vec::new();
+    collect_state_files(dir.as_ref(), &mut files);
+    files.sort();
+    files.dedup();
+    files
+        .into_iter()
+        .filter_map(|path| {
+            let saved = load_state(path.to_str()?)
+                .map_err(|e| {
+                    warn!("{:#}", e);
+                    eprintln!("{:#}", e);
+                })
+                .ok()?;
+            let volume = saved.volume;
+            Some(SavedState {
+                completed: volume.chapters.len() - volume.pending_chapter_indices.len(),
+                total: volume.chapters.len(),
+                name: volume.name,
+                url: volume.url,
+                header: saved.header,
+                path,
+            })
+        })
+        .collect()
+}
+
+fn collect_state_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            collect_state_files(&path, files);
+            continue;
+        }
+        let Some(name) = path.to_str() else {
+            continue;
+        };
+        // 只剩下备份时也可以恢复
+        let name = name.strip_suffix(".bak").unwrap_or(name);
+        if name.ends_with(".state.json") {
+            files.push(PathBuf::from(name));
+        }
+    }
+}
+
+/// 读取下载状态，旧版本的状态文件会被升级，状态文件损坏时使用上一次保存的状态
+pub fn load_state(path: &str) -> Result<StateFile> {
+    snapshot::load_with_backup(path, read_state)
+}
+
+fn read_state(path: &Path) -> Result<StateFile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("读取状态文件失败：{}", path.display()))?;
+    let value: Value = serde_json::from_str(&content)
+        .with_context(|| format!("解析状态文件失败：{}", path.display()))?;
+    let mut state =
+        migrate(value, path).with_context(|| format!("无法读取状态文件：{}", path.display()))?;
+    // 旧的状态文件中没有封面时为空字符串
+    let volume = &mut state.volume;
+    if volume.cover.as_ref().is_some_and(|c| c.url.is_empty()) {
+        volume.cover = None;
+    }
+    Ok(state)
+}
+
+/// 将状态文件逐个版本升级到当前版本，`path` 为状态文件的路径
+fn migrate(mut value: Value, path: &Path) -> Result<StateFile> {
+    let mut format = match value.pointer("/header/format") {
+        Some(format) => format
+            .as_u64()
+            .and_then(|f| u32::try_from(f).ok())
+            .ok_or_else(|| anyhow!("状态文件的格式版本无效：{}", format))?,
+        None => 1,
+    };
+    if format > STATE_FORMAT {
+        bail!(
+            "状态文件的格式版本为{}，当前程序只支持到版本{}，请使用新版本的程序恢复下载，或者删除下载数据后重新下载",
+            format,
+            STATE_FORMAT
+        );
+    }
+    if format < STATE_FORMAT {
+        info!("升级状态文件：版本{} -> 版本{}", format, STATE_FORMAT);
+    }
+    while format < STATE_FORMAT {
+        value = match format {
+            1 => migrate_v1(value, path)?,
+            _ => unreachable!(),
+        };
+        format += 1;
+    }
+    serde_json::from_value(value).map_err(|e| {
+        anyhow!(
+            "状态文件不符合版本{}的格式，无法恢复下载，请删除下载数据后重新下载：{}",
+            STATE_FORMAT,
+            e
+        )
+    })
+}
+
+/// 版本1是直接保存的卷（更早是 `Novel`），加上文件头，来源和书籍号从路径得到
+fn migrate_v1(mut value: Value, path: &Path) -> Result<Value> {
+    let Some(volume) = value.as_object_mut() else {
+        bail!("版本1的状态文件应该是一个对象");
+    };
+    // `Novel` 的作者、标签和简介在最外层，移动到元数据中
+    if !volume.contains_key("metadata") {
+        let mut metadata = serde_json::Map::new();
+        for key in ["author", "description"] {
+            if let Some(field) = volume.remove(key) {
+                metadata.insert(key.to_string(), field);
+            }
+        }
+        if let Some(tags) = volume.remove("tags") {
+            metadata.insert("tags".to_string(), json!(legacy_tags(&tags)));
+        }
+        volume.insert("metadata".to_string(), Value::Object(metadata));
+    }
+    let (source, book_id) = source_from_path(path, Path::new(DOWNLOAD_DIR)).unwrap_or_default();
+    Ok(json!({
+        "header": {
+            "format": 2,
+            "tool_version": "",
+            "source": source,
+            "book_id": book_id,
+            "created": "",
+        },
+        "volume": value,
+    }))
+}
+
+/// `Novel` 的标签 `{"state": "", "label": [], "span": []}`，顺序与转换时相同
+fn legacy_tags(tags: &Value) -> Vec<String> {
+    let state = tags.get("state").and_then(Value::as_str);
+    let lists = ["label", "span"]
+        .into_iter()
+        .filter_map(|key| tags.get(key).and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str);
+    state
+        .into_iter()
+        .chain(lists)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 按 `来源/书籍号/卷号` 保存的状态文件的来源和书籍号，旧的按卷名保存的文件返回 None
+fn source_from_path(path: &Path, download_dir: &Path) -> Option<(String, String)> {
+    let relative = path.strip_prefix(download_dir).ok()?;
+    let parts: Vec<&str> = relative.iter().map(|c| c.to_str()).collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [source, book_id, _] => Some((source.to_string(), book_id.to_string())),
+        _ => None,
+    }
+}
+
+/// 保存下载状态，先写入临时文件再替换，原来的状态保留为 `.bak`
+pub fn save_state(path: &str, header: &StateHeader, volume: &Volume) -> Result<()> {
+    #[derive(Serialize)]
+    struct StateFileRef<'a> {
+        header: &'a StateHeader,
+        volume: &'a Volume,
+    }
+
+    let state = StateFileRef { header, volume };
+    snapshot::write_atomic(path, |writer| Ok(serde_json::to_writer_pretty(writer, &state)?))
+        .with_context(|| format!("保存状态文件失败：{}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_legacy_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.state.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "url": "https://www.linovelib.com/novel/2356/vol_1.html",
+                "name": "第一卷",
+                "author": "作者",
+                "cover": "",
+                "tags": {"state": "连载", "label": ["文库"], "span": ["恋爱", ""]},
+                "description": "简介",
+                "chapters": [
+                    {"url": "https://www.linovelib.com/novel/2356/1.html", "title": "序章",
+                     "context": ["<p>a</p>"], "image": ["https://img/1.jpg"]}
+                ],
+                "pending_chapter_indices": [0]
+            }"#,
+        )
+        .unwrap();
+
+        let state = load_state(path.to_str().unwrap()).unwrap();
+        assert_eq!(state.header.format, STATE_FORMAT);
+        assert!(state.header.source.is_empty());
+        let volume = state.volume;
+        assert_eq!(volume.name, "第一卷");
+        assert!(volume.cover.is_none());
+        assert_eq!(volume.metadata.author, "作者");
+        assert_eq!(volume.metadata.description, "简介");
+        assert_eq!(volume.metadata.tags, vec!["连载", "文库", "恋爱"]);
+        assert_eq!(volume.chapters[0].pages, vec!["<p>a</p>"]);
+        assert_eq!(volume.chapters[0].images[0].url, "https://img/1.jpg");
+        assert_eq!(volume.pending_chapter_indices, vec![0]);
+
+        let header = StateHeader::new("bilinovel", "2356");
+        save_state(path.to_str().unwrap(), &header, &volume).unwrap();
+        let reloaded = load_state(path.to_str().unwrap()).unwrap();
+        assert_eq!(reloaded.header, header);
+        assert_eq!(reloaded.volume.chapters[0].pages, volume.chapters[0].pages);
+    }
+
+    #[test]
+    fn test_state_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let download = dir.path().join("download");
+        let images = dir.path().join("images");
+        let (download, images) = (download.to_str().unwrap(), images.to_str().unwrap());
+        let book = Book::new("bilinovel", "2356", "https://www.linovelib.com/novel/2356.html", "书");
+        let mut volume = Volume::new("https://www.linovelib.com/novel/2356/vol_1.html", "第一卷");
+
+        // 没有卷号时使用链接的哈希，同名的卷不会冲突
+        let other = Volume::new("https://www.linovelib.com/novel/2356/vol_2.html", "第一卷");
+        let paths = StatePaths::in_dirs(download, images, &book, &volume);
+        assert_ne!(paths, StatePaths::in_dirs(download, images, &book, &other));
+        volume.id = "1/..:?".to_string();
+        let paths = StatePaths::in_dirs(download, images, &book, &volume);
+        assert_eq!(paths.state, format!("{}/bilinovel/2356/1_____.state.json", download));
+
+        // 旧的按卷名保存的数据属于这一卷时会被移动
+        let legacy = format!("{}/第一卷.state.json", download);
+        std::fs::create_dir_all(download).unwrap();
+        save_state(&legacy, &StateHeader::new("bilinovel", "1"), &volume).unwrap();
+        paths.adopt_legacy(download, images, &volume);
+        assert!(!paths.exists());
+        save_state(&legacy, &paths.header, &volume).unwrap();
+        paths.adopt_legacy(download, images, &volume);
+        assert!(paths.exists());
+        assert!(!snapshot::exists(&legacy));
+
+        let states = list_states(download);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].name, "第一卷");
+        assert_eq!(states[0].header.book_id, "2356");
+    }
+
+    #[test]
+    fn test_source_from_path() {
+        let dir = Path::new("./temp/download");
+        assert_eq!(
+            source_from_path(Path::new("./temp/download/bilinovel/2356/1.state.json"), dir),
+            Some(("bilinovel".to_string(), "2356".to_string()))
+        );
+        assert_eq!(
+            source_from_path(Path::new("./temp/download/第一卷.state.json"), dir),
+            None
+        );
+    }
+
+    #[test]
+    fn test_newer_state_format() {
+        let value = json!({"header": {"format": STATE_FORMAT + 1}, "volume": {}});
+        let err = migrate(value, Path::new("")).err().unwrap();
+        assert!(err.to_string().contains("请使用新版本的程序"));
+    }
+}
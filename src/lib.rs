@@ -0,0 +1,36 @@
+//! 轻小说打包器
+//!
+//! 从小说网站下载轻小说并打包为epub。命令行程序只是这个库的一个使用者，
+//! 其他程序可以直接调用下载、解析和打包的接口。
+//!
+//! 新增网站只需要实现 [`Source`]，调度、状态保存、检查和打包由 [`Pipeline`] 完成。
+//! 布局简单的网站也可以只写一个 [`SiteDefinition`]，由通用来源按选择器解析。
+//!
+//! 可选功能：
+//! - `browser`：通过浏览器和single-file下载页面
+//! - `server`：接收浏览器扩展上传图片的http服务器
+//! - `epub`：打包epub
+#![cfg_attr(debug_assertions, allow(dead_code))]
+
+pub mod core;
+pub mod source;
+pub mod utils;
+
+pub use crate::core::check::{CheckResult, check_volume};
+pub use crate::core::get_struct::get_struct_by_url;
+pub use crate::core::init::{init_url_parser, register_site, register_url_handler};
+pub use crate::core::model::{Book, ImageRef, Metadata, Volume};
+pub use crate::core::options::{DownloadOptions, VolumeSelection};
+pub use crate::core::report::{BookReport, VolumeReport};
+pub use crate::core::pipeline::{Pipeline, PipelineConfig};
+pub use crate::core::site::{SiteDefinition, SiteSelectors};
+pub use crate::core::source::{Phase, Source};
+pub use crate::source::bilinovel::extract::extract_chapter;
+pub use crate::source::bilinovel::types::{BiliNovel, Chapter, Novel};
+pub use crate::utils::config::DynamicConfig;
+#[cfg(feature = "browser")]
+pub use crate::utils::download::downl::down::download_from_url;
+#[cfg(feature = "epub")]
+pub use crate::utils::epub::EpubGenerator;
+pub use crate::utils::httpclient::http_async::AsyncHttpClient;
+pub use crate::utils::httpserver::{AppConfig, Controller, ImageData};
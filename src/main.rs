@@ -1,10 +1,16 @@
-#![cfg_attr(debug_assertions, allow(dead_code))]
-
-mod core;
-mod source;
-mod utils;
-use crate::core::{get_struct::get_from_url, init::init_url_parser};
-use anyhow::Result;
+mod cli;
+use crate::cli::{Cli, Command};
+use novel_packer::core::{
+    batch::{load_batch_file, run_batch},
+    get_struct::{get_from_url, get_struct_by_url},
+    init::init_url_parser,
+    options::DownloadOptions,
+    report::{VolumeReport, print_summary},
+    state::{DOWNLOAD_DIR, IMAGES_DIR, list_states},
+};
+use novel_packer::utils::fetch::record::set_fetch_mode;
+use anyhow::{Result, anyhow};
+use clap::Parser;
 use chrono::Local;
 use std::{env, io::stdin};
 use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
@@ -12,10 +18,12 @@ use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
     std::fs::create_dir_all("./temp/temp")?;
     std::fs::create_dir_all("./temp/logs")?;
-    std::fs::create_dir_all("./temp/images")?;
-    std::fs::create_dir_all("./temp/download")?;
+    std::fs::create_dir_all(IMAGES_DIR)?;
+    std::fs::create_dir_all(DOWNLOAD_DIR)?;
     std::fs::create_dir_all("./output")?;
     init_url_parser();
 
@@ -77,7 +85,16 @@ async fn main() -> Result<()> {
         None
     };
     write_startup_info();
+    set_fetch_mode(cli.fetch_mode())?;
+
+    match cli.command {
+        Some(command) => run_command(command).await,
+        None => run_interactive().await,
+    }
+}
 
+/// 交互模式：所有问题都在终端询问
+async fn run_interactive() -> Result<()> {
     let mut novel = get_from_url();
 
     if let Err(e) = novel.check() {
@@ -90,7 +107,7 @@ async fn main() -> Result<()> {
         return wait_for_exit();
     }
 
-    if let Err(e) = novel.download().await {
+    if let Err(e) = novel.download(&DownloadOptions::interactive()).await {
         eprintln!("{}", e);
         return wait_for_exit();
     }
@@ -99,6 +116,129 @@ async fn main() -> Result<()> {
     wait_for_exit()
 }
 
+/// 命令行模式：不询问任何问题，出错时返回非零退出码
+async fn run_command(command: Command) -> Result<()> {
+    let options = command.options();
+
+    if let Command::Batch { file, .. } = &command {
+        let entries = load_batch_file(file)?;
+        let reports = run_batch(entries, &options).await;
+        print_summary(&reports);
+        let failed = reports.iter().filter(|r| !r.is_success()).count();
+        return if failed == 0 {
+            Ok(())
+        } else {
+            Err(anyhow!("{}本书处理失败", failed))
+        };
+    }
+
+    if let Command::ListStates = command {
+        print_states();
+        return Ok(());
+    }
+
+    if let Command::Validate { files } = &command {
+        return validate_files(files);
+    }
+
+    let url = command.url().unwrap_or_default();
+    let mut novel = get_struct_by_url(url)?;
+    match command {
+        Command::Info { .. } => novel.display().await,
+        Command::Chapters { .. } => {
+            novel.display().await?;
+            novel.display_chapters(&options).await
+        }
+        Command::Clean { .. } => {
+            novel.display().await?;
+            novel.clean(&options).await
+        }
+        Command::Download { .. } | Command::Check { .. } | Command::Resume { .. } => {
+            novel.check()?;
+            novel.display().await?;
+            check_reports(&novel.download(&options).await?)
+        }
+        Command::Pack { .. } => {
+            novel.display().await?;
+            check_reports(&novel.download(&options).await?)
+        }
+        Command::Batch { .. } | Command::ListStates | Command::Validate { .. } => unreachable!(),
+    }
+}
+
+/// 校验epub文件并显示结果
+#[cfg(feature = "epub")]
+fn validate_files(files: &[std::path::PathBuf]) -> Result<()> {
+    use novel_packer::utils::epub::validate::validate_epub;
+
+    let mut failed = 0;
+    for file in files {
+        match validate_epub(file) {
+            Ok(report) => {
+                println!("{}", report);
+                if !report.is_ok() {
+                    failed += 1;
+                }
+            }
+            Err(e) => {
+                eprintln!("{:#}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!("{}个文件没有通过校验", failed))
+    }
+}
+
+#[cfg(not(feature = "epub"))]
+fn validate_files(_files: &[std::path::PathBuf]) -> Result<()> {
+    Err(anyhow!("未启用 epub 功能，无法校验"))
+}
+
+/// 显示保存的下载状态
+fn print_states() {
+    let states = list_states(DOWNLOAD_DIR);
+    if states.is_empty() {
+        println!("没有可以恢复的下载");
+        return;
+    }
+    for state in &states {
+        let header = &state.header;
+        let source = if header.source.is_empty() {
+            "未知来源".to_string()
+        } else {
+            format!("{} {}", header.source, header.book_id)
+        };
+        println!(
+            "[{}] {}  已完成 {}/{}",
+            source, state.name, state.completed, state.total
+        );
+        println!("    卷链接：{}", state.url);
+        if !header.created.is_empty() {
+            println!("    开始时间：{}", header.created);
+        }
+        println!("    状态文件：{}", state.path.display());
+    }
+    println!("使用 resume <书籍链接> -v <卷> 恢复下载");
+}
+
+/// 有卷处理失败时返回错误
+fn check_reports(reports: &[VolumeReport]) -> Result<()> {
+    let failed: Vec<&str> = reports
+        .iter()
+        .filter(|r| r.error.is_some())
+        .map(|r| r.name.as_str())
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("以下卷处理失败：{}", failed.join("，")))
+    }
+}
+
 // 提取等待退出的逻辑为单独函数，复用
 fn wait_for_exit() -> Result<()> {
     println!("按回车退出...");
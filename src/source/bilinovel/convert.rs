@@ -0,0 +1,87 @@
+use regex::Regex;
+
+use crate::core::model::{self, Book, ImageRef, Metadata, Volume};
+use crate::source::bilinovel::types::{BiliNovel, Chapter, Novel, Tags};
+
+///来源网站名
+pub const SOURCE_NAME: &str = "bilinovel";
+
+impl Tags {
+    ///转换为通用的标签列表
+    pub fn to_vec(&self) -> Vec<String> {
+        std::iter::once(&self.state)
+            .chain(&self.label)
+            .chain(&self.span)
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect()
+    }
+}
+
+impl From<&Chapter> for model::Chapter {
+    fn from(chapter: &Chapter) -> Self {
+        model::Chapter {
+            url: chapter.url.clone(),
+            title: chapter.title.clone(),
+            pages: chapter.context.clone(),
+            images: chapter.image.iter().cloned().map(ImageRef::from).collect(),
+        }
+    }
+}
+
+impl Novel {
+    ///卷号，从链接中提取
+    pub fn volume_id(&self) -> String {
+        Regex::new(r"/vol_(\d+)")
+            .ok()
+            .and_then(|re| re.captures(&self.url))
+            .and_then(|ca| ca.get(1))
+            .map(|v| v.as_str().to_string())
+            .unwrap_or_default()
+    }
+
+    ///转换为通用的卷
+    pub fn to_volume(&self) -> Volume {
+        Volume {
+            id: self.volume_id(),
+            url: self.url.clone(),
+            name: self.name.clone(),
+            cover: (!self.cover.is_empty()).then(|| ImageRef::new(&self.cover)),
+            metadata: Metadata {
+                author: self.author.clone(),
+                description: self.description.clone(),
+                tags: self.tags.as_ref().map(Tags::to_vec).unwrap_or_default(),
+                ..Metadata::default()
+            },
+            chapters: self.chapters.iter().map(model::Chapter::from).collect(),
+            pending_chapter_indices: self.pending_chapter_indices.clone(),
+        }
+    }
+}
+
+impl BiliNovel {
+    ///书籍号，从链接中提取
+    pub fn book_id(&self) -> String {
+        Regex::new(r"/novel/(\d+)")
+            .ok()
+            .and_then(|re| re.captures(&self.url))
+            .and_then(|ca| ca.get(1))
+            .map(|v| v.as_str().to_string())
+            .unwrap_or_default()
+    }
+
+    ///转换为通用的书籍，需要先解析书籍页面
+    pub fn to_book(&self) -> Book {
+        let mut book = Book::new(SOURCE_NAME, &self.book_id(), &self.url, &self.book_name);
+        book.metadata = Metadata {
+            author: self.author.clone(),
+            description: self.description.clone(),
+            tags: self.tags.as_ref().map(Tags::to_vec).unwrap_or_default(),
+            // 标签中的 label 为文库
+            publisher: self.tags.as_ref().and_then(|t| t.label.first().cloned()),
+            ..Metadata::default()
+        };
+        book.volumes = self.volume.iter().map(Novel::to_volume).collect();
+        book
+    }
+}
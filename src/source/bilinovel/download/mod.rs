@@ -1,9 +1,11 @@
-use std::path::PathBuf;
+use std::path::Path;
+use crate::core::site::SiteDefinition;
 use crate::utils::config::DynamicConfig;
-use crate::utils::download::downl::down::download_from_url;
+use crate::utils::fetch::singlefile::SingleFileBackend;
+use crate::utils::fetch::Page;
 use anyhow::{Result, anyhow};
 use regex::Regex;
-use serde_json::{Value, json};
+use serde_json::json;
 
 
 ///根据给定的第一页的url下载章节页面，还需给定浏览器地址、爬虫会话文件地址、配置文件地址
@@ -11,10 +13,11 @@ pub async fn download_chapter_singlefile(
     url: &str,
     browser_server_url: &str, //浏览器地址
     crawl_path: &str,         //爬虫会话文件
-    config_path: &str,
-) -> Result<Vec<Value>> {
+    config_path: &Path,
+    site: &SiteDefinition,
+) -> Result<Vec<Page>> {
     let mut config = DynamicConfig::new();
-    config.load(PathBuf::from(config_path))?;
+    config.load(config_path.to_path_buf())?;
 
     let re = Regex::new(r"novel/\d+/(\d{2,})")?;
     let chapter_id = re
@@ -34,5 +37,7 @@ pub async fn download_chapter_singlefile(
                 format!("^https://www\\.linovelib\\.com/novel/(\\d+)/\\d+(_\\d+)?\\.html$ https://www.linovelib.com/novel/$1/{}$2.html",chapter_id),
                 "^https://www\\.linovelib\\.com/novel/\\d+/catalog.*$ https://www.linovelib.com/"]));
 
-    download_from_url(url, config).await
+    site.wrap(SingleFileBackend::new(config))
+        .fetch_pages(url)
+        .await
 }
@@ -1,58 +1,13 @@
-use std::path::Path;
-
 use anyhow::{Result, anyhow};
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 use tracing::error;
 use url::Url;
 use visdom::Vis;
 
-use crate::{source::bilinovel::types::{Chapter, Novel, Tags}, utils::epub::default_css::BROKEN_IMAGE_BASE64};
-
-///提取章节内容，并处理img标签
-pub fn extract_chapter(html: &str, selector: &str, src_name: &str, remove_vec: Vec<&str>) -> Result<(Vec<String>, String)> {
-    let html = Vis::load(Html::parse_document(html).html()).map_err(|e| anyhow!("html解析失败 {}", e))?;
-    let text = html.find(selector).first();
-    let items = text.children("");
-    // items.filter("div").remove();
-    items.filter("p.sf-hidden").remove();
-    
-    for se in remove_vec {
-        let items = text.children("");
-        items.filter(se).remove();
-    }
-    // items.filter_by(handle)
-    let mut src_vec = Vec::new();
-    text.find("img").for_each(|_index, ele| {
-        let mut img_ele = Vis::dom(ele);
-        // 2. 检查是否存在目标属性（src_name，比如 "data-src" 或 "src"）
-        if img_ele.has_attr(src_name) {
-            if let Some(attr_value) = img_ele.attr(src_name) {
-                let url = match Url::parse(&attr_value.to_string()) {
-                    Ok(u) => u.to_string(),
-                    Err(_) => String::new(),
-                };
-                let filename = Path::new(&url)
-                    .file_name() // 取最后一个组件（如"a/b/c.jpg"→"c.jpg"）
-                    .and_then(|os_str| os_str.to_str()) // 转&str（处理非UTF8）
-                    .unwrap_or(""); // 任何错误都返回空字符串
-
-                let src_val = attr_value.to_string();
-                if !filename.is_empty() {
-                    img_ele.set_attr("src", Some(&format!("images/{}", filename)));
-                    src_vec.push(src_val);
-                } else {
-                    img_ele.set_attr("src", Some(BROKEN_IMAGE_BASE64));
-                    src_vec.push(src_val);
-                }
-                
-            }
-        } else {
-            img_ele.set_attr("src", Some(BROKEN_IMAGE_BASE64));
-        }
-        true
-    });
-    Ok((src_vec, text.html().trim().to_string()))
-}
+use crate::core::site::SiteSelectors;
+use crate::source::bilinovel::types::{Chapter, Novel, Tags};
+use crate::utils::html::{parse_selector, select_links};
+pub use crate::utils::html::extract_chapter;
 
 ///提取作者名称
 pub fn extract_author(html: &str, selector: &str) -> Result<String> {
@@ -61,7 +16,7 @@ pub fn extract_author(html: &str, selector: &str) -> Result<String> {
             error!("{}", e);
             anyhow!("{e}")
         })?;
-    let author = html.select(&se).next().map_or_else(||String::new(), |s| s.text().collect::<String>());
+    let author = html.select(&se).next().map_or_else(String::new, |s| s.text().collect::<String>());
     Ok(author)
 }
 
@@ -73,7 +28,7 @@ pub fn build_chapter(html: &str, selector: &str) -> Result<Vec<Chapter>> {
     let mut res = Vec::new();
     items.for_each(|_index, ele| {
         let chapter_ele = Vis::dom(ele);
-        let url = chapter_ele.children("").first().attr("href").map_or_else(||String::new(), |v| v.to_string());
+        let url = chapter_ele.children("").first().attr("href").map_or_else(String::new, |v| v.to_string());
         let title = chapter_ele.children("").first().text();
         res.push(Chapter::new(&url, &title));
         true
@@ -81,201 +36,167 @@ pub fn build_chapter(html: &str, selector: &str) -> Result<Vec<Chapter>> {
     Ok(res)
 }
 
-///提取描述
-pub fn extract_description(html: &str, selector: &str) -> Result<(String, String, String)> {
+///提取描述，返回（统计数据，公告，简介）
+pub fn extract_description(html: &str, selectors: &SiteSelectors) -> Result<(String, String, String)> {
     let html = Html::parse_document(html);
-    let selector = Selector::parse(selector).map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let nums_selector = Selector::parse("div.nums > span").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-
-    let des_selector = Selector::parse("div.book-dec>p").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-
-    let notice_selector = Selector::parse("div.notice").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
+    let selector = parse_selector(&selectors.description)?;
+    let nums_selector = optional_selector(&selectors.stats)?;
+    let des_selector = optional_selector(&selectors.intro)?;
+    let notice_selector = optional_selector(&selectors.notice)?;
 
     let mut nums = String::new();
     let mut notice = String::new();
     let mut description = String::new();
     if let Some(element) = html.select(&selector).next() {
-        let nums_txt = element
-            .select(&nums_selector)
-            .map(|span| {
-                span.text()
-                    .fold(String::new(), |mut s, t| {
-                        s.push_str(t);
-                        s
-                    })
-                    .trim()
-                    .to_string()
-            })
-            .collect::<Vec<_>>()
-            .join("\t");
-        let des = element
-            .select(&des_selector)
-            .next()
-            .map_or_else(
-                || String::new(),
-                |s| {
-                    html2text::from_read(s.html().as_bytes(), 100).unwrap_or("提取失败".to_string())
-                },
-            )
-            .trim()
-            .to_string();
-        let notice_txt = element
-            .select(&notice_selector)
-            .next()
-            .map_or_else(
-                || String::new(),
-                |s| {
-                    html2text::from_read(s.html().as_bytes(), 100).unwrap_or("提取失败".to_string())
-                },
-            )
-            .trim()
-            .to_string();
-
-        nums.push_str(&nums_txt);
-        description.push_str(&des);
-        notice.push_str(&notice_txt);
+        if let Some(nums_selector) = &nums_selector {
+            let nums_txt = element
+                .select(nums_selector)
+                .map(|span| element_text(&span))
+                .collect::<Vec<_>>()
+                .join("\t");
+            nums.push_str(&nums_txt);
+        }
+        if let Some(des_selector) = &des_selector {
+            description.push_str(&first_as_text(&element, des_selector));
+        }
+        if let Some(notice_selector) = &notice_selector {
+            notice.push_str(&first_as_text(&element, notice_selector));
+        }
     }
 
     Ok((nums, notice, description))
 }
 
 ///提取标签
-pub fn extract_tags(html: &str, selector: &str) -> Result<Tags> {
+pub fn extract_tags(html: &str, selectors: &SiteSelectors) -> Result<Tags> {
     let html = Html::parse_document(html);
-    let selector = Selector::parse(selector).map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let state_selector = Selector::parse("a.state").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let label_selector = Selector::parse("a.label").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let span_selector = Selector::parse("span>a").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
+    let selector = parse_selector(&selectors.tags)?;
+    let state_selector = optional_selector(&selectors.state)?;
+    let label_selector = optional_selector(&selectors.label)?;
+    let span_selector = optional_selector(&selectors.tag_link)?;
     let mut res = Tags::new();
     if let Some(element) = html.select(&selector).next() {
-        res.state = element
-            .select(&state_selector)
-            .next()
-            .map_or_else(|| String::new(), |s| s.text().collect::<String>());
-
-        res.label = element
-            .select(&label_selector)
-            .map(|label| {
-                label
-                    .text()
-                    .fold(String::new(), |mut s, t| {
-                        s.push_str(t);
-                        s
-                    })
-                    .trim()
-                    .to_string()
-            })
-            .collect();
-
-        res.span = element
-            .select(&span_selector)
-            .map(|span| {
-                span.text()
-                    .fold(String::new(), |mut s, t| {
-                        s.push_str(t);
-                        s
-                    })
-                    .trim()
-                    .to_string()
-            })
-            .collect()
+        if let Some(state_selector) = &state_selector {
+            res.state = element
+                .select(state_selector)
+                .next()
+                .map_or_else(String::new, |s| s.text().collect::<String>());
+        }
+        if let Some(label_selector) = &label_selector {
+            res.label = element.select(label_selector).map(|e| element_text(&e)).collect();
+        }
+        if let Some(span_selector) = &span_selector {
+            res.span = element.select(span_selector).map(|e| element_text(&e)).collect();
+        }
     }
     Ok(res)
 }
 
-///提取所有的章节
-pub fn extract_volume(html: &str, selector: &str) -> Result<Vec<Novel>> {
+///提取书籍页中的所有卷，链接按 `base_url` 补全
+pub fn extract_volume(html: &str, selectors: &SiteSelectors, base_url: &str) -> Result<Vec<Novel>> {
     let html = Html::parse_document(html);
-    let volume_selector = Selector::parse("a").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let selector = Selector::parse(selector).map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let mut res = Vec::new();
-    if let Some(element) = html.select(&selector).next() {
-        res = element
-            .select(&volume_selector)
-            .filter_map(|e| {
-                let mut url = e.value().attr("href").unwrap_or_default().to_string();
-                if let Ok(full_url) = Url::parse(&url) {
-                    url = full_url.to_string();
-                } else if Url::parse(&format!("https://www.linovelib.com{}", url)).is_ok() {
-                    url = format!("https://www.linovelib.com{}", url);
-                }
-
-                let name = e.value().attr("title").unwrap_or_default().to_string();
-                Some(Novel::new(url, name))
-            })
-            .collect::<Vec<_>>();
-        res.reverse();
-    }
+    let name_attr = (!selectors.volume_name_attr.is_empty()).then_some(selectors.volume_name_attr.as_str());
+    let mut res = select_links(&html, &selectors.volumes, &selectors.volume_link, name_attr, base_url)?
+        .into_iter()
+        .map(|(url, name)| Novel::new(url, name))
+        .collect::<Vec<_>>();
+    res.reverse();
     Ok(res)
 }
 
-///提取所有的章节
-pub fn extract_volume_catalog(html: &str, selector: &str) -> Result<Vec<Novel>> {
+///提取目录页中的所有卷，链接按 `base_url` 补全
+pub fn extract_volume_catalog(html: &str, selectors: &SiteSelectors, base_url: &str) -> Result<Vec<Novel>> {
     let html = Html::parse_document(html);
-    let volume_selector = Selector::parse("div.volume").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let volume_selector_url = Selector::parse("a.volume-cover").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let volume_selector_name = Selector::parse("div.volume-info>h2").map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
-    let selector = Selector::parse(selector).map_err(|e| {
-        error!("{}", e);
-        anyhow!("{e}")
-    })?;
+    let selector = parse_selector(&selectors.catalog_volumes)?;
+    let volume_selector = parse_selector(&selectors.catalog_volume)?;
+    let volume_selector_url = parse_selector(&selectors.catalog_volume_link)?;
+    let volume_selector_name = parse_selector(&selectors.catalog_volume_name)?;
+    let base = Url::parse(base_url).ok();
     let mut res = Vec::new();
     if let Some(element) = html.select(&selector).next() {
         res = element
             .select(&volume_selector)
-            .filter_map(|e| {
-                let mut url = e.select(&volume_selector_url).next().map_or_else(|| String::new(), |e| e.value().attr("href").unwrap_or_default().to_string());
-                // let mut url = e.value().attr("href").unwrap_or_default().to_string();
-                if let Ok(full_url) = Url::parse(&url) {
-                    url = full_url.to_string();
-                } else if Url::parse(&format!("https://www.linovelib.com{}", url)).is_ok() {
-                    url = format!("https://www.linovelib.com{}", url);
-                }
-
-                // let name = e.value().attr("title").unwrap_or_default().to_string();
-                let name = e.select(&volume_selector_name).next().map_or_else(|| String::new(), |e| e.text().collect::<String>());
-                Some(Novel::new(url, name))
+            .map(|e| {
+                let href = e
+                    .select(&volume_selector_url)
+                    .next()
+                    .and_then(|e| e.value().attr("href"))
+                    .unwrap_or_default();
+                let url = match &base {
+                    Some(base) => base.join(href).map(|u| u.to_string()).unwrap_or_default(),
+                    None => href.to_string(),
+                };
+                let name = e
+                    .select(&volume_selector_name)
+                    .next()
+                    .map_or_else(String::new, |e| e.text().collect::<String>());
+                Novel::new(url, name)
             })
             .collect::<Vec<_>>();
     }
     Ok(res)
 }
+
+///选择器为空时不提取
+fn optional_selector(selector: &str) -> Result<Option<Selector>> {
+    if selector.is_empty() {
+        return Ok(None);
+    }
+    parse_selector(selector).map(Some)
+}
+
+///元素的文本，去掉首尾空白
+fn element_text(element: &ElementRef) -> String {
+    element.text().collect::<String>().trim().to_string()
+}
+
+///第一个匹配元素转换成的纯文本
+fn first_as_text(element: &ElementRef, selector: &Selector) -> String {
+    element
+        .select(selector)
+        .next()
+        .map_or_else(String::new, |s| {
+            html2text::from_read(s.html().as_bytes(), 100).unwrap_or("提取失败".to_string())
+        })
+        .trim()
+        .to_string()
+}
+
+///章节的下一页链接，分页为 `{章节号}_{页码}.html`，页面中没有指向下一页的链接时返回None
+pub fn next_page_url(url: &str, html: &str, page: usize) -> Option<String> {
+    let (stem, _) = url.rsplit_once(".html")?;
+    let (_, name) = stem.rsplit_once('/')?;
+    // 第一页的链接没有页码
+    let chapter_id = name.split('_').next()?;
+    let next = format!("{}_{}.html", chapter_id, page);
+    if !html.contains(&format!("/{}", next)) {
+        return None;
+    }
+    let base = stem.rsplit_once('/')?.0;
+    Some(format!("{}/{}", base, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_page_url() {
+        let html = r#"<a href="/novel/2356/86524_2.html">下一页</a>"#;
+        assert_eq!(
+            next_page_url("https://www.linovelib.com/novel/2356/86524.html", html, 2).as_deref(),
+            Some("https://www.linovelib.com/novel/2356/86524_2.html")
+        );
+        let html = r#"<a href="/novel/2356/86524_3.html">下一页</a>"#;
+        assert_eq!(
+            next_page_url("https://www.linovelib.com/novel/2356/86524_2.html", html, 3).as_deref(),
+            Some("https://www.linovelib.com/novel/2356/86524_3.html")
+        );
+        // 最后一页指向下一章
+        let html = r#"<a href="/novel/2356/86525.html">下一章</a>"#;
+        assert_eq!(
+            next_page_url("https://www.linovelib.com/novel/2356/86524_3.html", html, 4),
+            None
+        );
+    }
+}
@@ -1,5 +1,7 @@
 pub mod types;
 pub mod parser;
+#[cfg(feature = "browser")]
 pub mod download;
 pub mod extract;
-pub mod singlefile;
\ No newline at end of file
+pub mod source;
+pub mod convert;
\ No newline at end of file
@@ -0,0 +1,208 @@
+#[cfg(feature = "browser")]
+use std::path::PathBuf;
+
+use crate::core::model::{self, Book, Volume};
+use crate::core::site::ChapterBackend;
+#[cfg(feature = "browser")]
+use crate::core::source::Phase;
+use crate::core::source::Source;
+use crate::source::bilinovel::convert::SOURCE_NAME;
+use crate::source::bilinovel::types::{BiliNovel, Chapter, Novel, Tags};
+#[cfg(feature = "browser")]
+use crate::utils::browser::browser_server::{BrowserConfig, BrowserServer};
+#[cfg(feature = "browser")]
+use crate::utils::check_single_file;
+#[cfg(feature = "browser")]
+use crate::utils::config::DynamicConfig;
+use crate::utils::fetch::images::download_missing_images;
+use crate::utils::fetch::{self, record};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+#[cfg(feature = "browser")]
+use serde_json::json;
+#[cfg(feature = "browser")]
+use tempfile::NamedTempFile;
+#[cfg(feature = "browser")]
+use tracing::info;
+use tracing::warn;
+
+#[async_trait]
+impl Source for BiliNovel {
+    fn name(&self) -> &str {
+        SOURCE_NAME
+    }
+
+    #[cfg(feature = "browser")]
+    fn check(&self) -> Result<()> {
+        if record::is_replay() {
+            return Ok(());
+        }
+        let result =
+            check_single_file::check_exe("./extra/single-file.exe").map_err(|s| anyhow!("{}", s));
+        match result {
+            // http下载只在回退时需要single-file
+            Err(e) if self.site.backend == ChapterBackend::Http => {
+                warn!("{}，遇到反爬页面时无法改用single-file", e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    async fn fetch_book_info(&mut self) -> Result<Book> {
+        self.parser_book_http_async().await?;
+        // self.parser_book_singlefile(dconfig).await?;
+        let mut book = self.to_book();
+        book.volumes.clear();
+        Ok(book)
+    }
+
+    async fn list_volumes(&mut self, _book: &Book) -> Result<Vec<Volume>> {
+        Ok(self.volume.iter().map(Novel::to_volume).collect())
+    }
+
+    async fn list_chapters(&self, volume: &mut Volume) -> Result<()> {
+        let mut novel = Novel::new(volume.url.clone(), volume.name.clone());
+        let by_http = match self.site.backend {
+            ChapterBackend::Http => novel
+                .parser_by_http(&*self.fetcher, &self.site)
+                .await
+                .map_err(|e| warn!("http解析卷页面失败，改用single-file：{}", e))
+                .is_ok(),
+            ChapterBackend::SingleFile => false,
+        };
+        if !by_http {
+            self.novel_by_singlefile(&mut novel).await?;
+        }
+
+        volume.metadata.author = novel.author;
+        volume.metadata.description = novel.description;
+        volume.metadata.tags = novel.tags.as_ref().map(Tags::to_vec).unwrap_or_default();
+        volume.cover = (!novel.cover.is_empty()).then(|| model::ImageRef::new(&novel.cover));
+        volume.chapters = novel.chapters.iter().map(model::Chapter::from).collect();
+        // 回放时single-file下载的图片也从录制中读取
+        if (by_http || record::is_replay())
+            && let Some(cover) = &volume.cover
+        {
+            download_missing_images(&*self.fetcher, std::slice::from_ref(cover)).await;
+        }
+        Ok(())
+    }
+
+    async fn fetch_chapter(&self, chapter: &model::Chapter) -> Result<model::Chapter> {
+        let mut new_chapter = Chapter::new(&chapter.url, &chapter.title);
+        let mut http_error = None;
+        if self.site.backend == ChapterBackend::Http {
+            match new_chapter.parser_by_http(&*self.fetcher, &self.site).await {
+                Ok(()) => return Ok(model::Chapter::from(&new_chapter)),
+                Err(e) => {
+                    warn!("http下载章节失败，改用single-file：{}", e);
+                    http_error = Some(e);
+                }
+            }
+            new_chapter = Chapter::new(&chapter.url, &chapter.title);
+        }
+        if let Err(e) = self.chapter_by_singlefile(&mut new_chapter).await {
+            // 回退也失败时保留拦截的错误，让下载流程降低并发
+            return Err(match http_error {
+                Some(http_error) if fetch::is_blocked(&http_error) && !fetch::is_blocked(&e) => {
+                    http_error
+                }
+                _ => e,
+            });
+        }
+        let chapter = model::Chapter::from(&new_chapter);
+        if record::is_replay() {
+            download_missing_images(&*self.fetcher, &chapter.images).await;
+        }
+        Ok(chapter)
+    }
+
+    #[cfg(feature = "browser")]
+    async fn begin(&mut self, phase: Phase) -> Result<()> {
+        //浏览器的配置，检查时使用单独的配置
+        self.browser_config = match phase {
+            Phase::Download => "./config/browser.json",
+            Phase::Check => "./config/browser_check.json",
+        }
+        .to_string();
+        // http下载时等到需要回退再启动浏览器，回放时不需要浏览器
+        if self.site.backend == ChapterBackend::SingleFile && !record::is_replay() {
+            self.browser_url().await?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "browser")]
+    async fn end(&mut self, _phase: Phase) {
+        if let Some(mut browser) = self.browser.get_mut().take() {
+            browser.stop();
+        }
+    }
+
+    fn display(&self, _book: &Book) {
+        println!("{}", self);
+    }
+}
+
+#[cfg(feature = "browser")]
+impl BiliNovel {
+    ///浏览器地址，浏览器没有启动时先启动
+    async fn browser_url(&self) -> Result<String> {
+        if record::is_replay() {
+            return Ok(String::new());
+        }
+        let mut guard = self.browser.lock().await;
+        if let Some(browser) = guard.as_ref() {
+            return Ok(browser.get_server_url());
+        }
+        let config_path = if self.browser_config.is_empty() {
+            "./config/browser.json"
+        } else {
+            &self.browser_config
+        };
+        let mut browser = BrowserServer::new(BrowserConfig::load(config_path)?)?;
+        browser.start().await?;
+        let url = browser.get_server_url();
+        info!("浏览器已启动：{}", url);
+        *guard = Some(browser);
+        Ok(url)
+    }
+
+    ///通过single-file解析卷的页面
+    async fn novel_by_singlefile(&self, novel: &mut Novel) -> Result<()> {
+        //解析小说页面的配置
+        let mut config = DynamicConfig::new();
+        config.load(PathBuf::from("./config/novel.json"))?;
+        config.with_set("browser-server", json!(self.browser_url().await?));
+        novel
+            .parser_by_singlefile(config, &self.site)
+            .await
+    }
+
+    ///通过single-file下载章节
+    async fn chapter_by_singlefile(&self, chapter: &mut Chapter) -> Result<()> {
+        // 每个章节使用单独的爬虫会话文件
+        let temp_file = NamedTempFile::new_in("./temp/temp")?;
+        let crawl_path = temp_file
+            .path()
+            .to_str()
+            .ok_or_else(|| anyhow!("临时文件路径无效"))?
+            .to_string();
+        let browser_server_url = self.browser_url().await?;
+        chapter
+            .parser_by_singlefile(&browser_server_url, &crawl_path, &self.site)
+            .await
+    }
+}
+
+#[cfg(not(feature = "browser"))]
+impl BiliNovel {
+    async fn novel_by_singlefile(&self, _novel: &mut Novel) -> Result<()> {
+        Err(anyhow!("未启用 browser 功能，无法使用single-file下载"))
+    }
+
+    async fn chapter_by_singlefile(&self, _chapter: &mut Chapter) -> Result<()> {
+        Err(anyhow!("未启用 browser 功能，无法使用single-file下载"))
+    }
+}
@@ -1,6 +1,5 @@
 
 
-#[derive(Default)]
 pub struct BiliNovel {
     pub url: String,
     pub catalog: String,
@@ -11,18 +10,16 @@ pub struct BiliNovel {
     pub notice: String,
     pub description: String,
     pub volume: Vec<Novel>,
-    pub index: Vec<u8>,
-    pub config: NovelConfig
-}
-
-#[derive(Default, Clone, Serialize, Deserialize)]
-pub struct  NovelConfig {
-    pub max_concurrent: usize,
-    pub check_rounds: usize,
-    pub css: String,
-    pub compression_level: u32,
-    pub check_concurrent: usize,
-    pub save_interval: usize
+    ///下载或检查时启动的浏览器，http下载时只在需要回退到single-file时启动
+    #[cfg(feature = "browser")]
+    pub browser: tokio::sync::Mutex<Option<BrowserServer>>,
+    ///启动浏览器使用的配置文件
+    #[cfg(feature = "browser")]
+    pub browser_config: String,
+    ///http下载使用的后端，测试时可以替换为回放
+    pub fetcher: Fetcher,
+    ///网站定义，提供解析用的选择器
+    pub site: Arc<SiteDefinition>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -71,43 +68,50 @@ impl Chapter {
 
 impl Novel {
     pub fn new(url: String, name: String) -> Self {
-        let mut res = Novel::default();
-        res.url = url;
-        res.name = name;
-        res
+        Novel {
+            url,
+            name,
+            ..Novel::default()
+        }
     }
 }
 
 impl BiliNovel {
-    pub fn new(url: String, catalog: String) -> Self {
-        let mut res = BiliNovel::default();
-        res.url = url;
-        res.catalog = catalog;
-        res
-    }
-}
-
-use anyhow::{Result};
-impl NovelConfig {
-    /// 从文件读取配置
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        // 读取文件内容
-        let content = std::fs::read_to_string(path)?;
-        // 反序列化
-        let config = serde_json::from_str(&content)?;
-        Ok(config)
+    pub fn new(url: String, catalog: String, site: Arc<SiteDefinition>) -> Result<Self> {
+        Ok(BiliNovel {
+            url,
+            catalog,
+            book_name: String::new(),
+            author: String::new(),
+            tags: None,
+            nums: String::new(),
+            notice: String::new(),
+            description: String::new(),
+            volume: Vec::new(),
+            #[cfg(feature = "browser")]
+            browser: tokio::sync::Mutex::new(None),
+            #[cfg(feature = "browser")]
+            browser_config: String::new(),
+            fetcher: site.fetcher()?,
+            site,
+        })
     }
 }
 
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 use core::fmt;
-use std::path::Path;
+use std::sync::Arc;
+use anyhow::Result;
+use crate::core::site::SiteDefinition;
+use crate::utils::fetch::Fetcher;
+#[cfg(feature = "browser")]
+use crate::utils::browser::browser_server::BrowserServer;
 
 impl fmt::Display for BiliNovel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}\n", format!("{}",self.book_name).bright_yellow().bold())?;
-        writeln!(f, "{}\n",format!("{}",self.author).blue())?;
+        writeln!(f, "{}\n", self.book_name.to_string().bright_yellow().bold())?;
+        writeln!(f, "{}\n",self.author.to_string().blue())?;
         if let Some(tags) = &self.tags {
             writeln!(f, "{}", tags)?;
         }
@@ -116,7 +120,7 @@ impl fmt::Display for BiliNovel {
         }
         if !self.notice.is_empty() {
             for line in self.notice.split("\n") {
-                writeln!(f, "{}", format!("{}",line).on_truecolor(160, 125, 125))?
+                writeln!(f, "{}", line.to_string().on_truecolor(160, 125, 125))?
             }
             ;
         }
@@ -124,7 +128,7 @@ impl fmt::Display for BiliNovel {
             writeln!(f, "\n{}\n", self.description)?;
         }
         for (i, v) in self.volume.iter().enumerate() {
-            writeln!(f, "[{}]\t{}", i, format!("{}",v.name).underline())?;
+            writeln!(f, "[{}]\t{}", i, v.name.to_string().underline())?;
         }
         Ok(())
     }
@@ -132,18 +136,18 @@ impl fmt::Display for BiliNovel {
 
 impl fmt::Display for Tags {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}  ", format!("{}", self.state).on_truecolor(21, 164, 11))?;
+        write!(f, "{}  ", self.state.to_string().on_truecolor(21, 164, 11))?;
 
         let (r, mut g1,mut g) = (151, 120, 20);
         for l in &self.label {
-            write!(f, "{}  ", format!("{}", l).on_truecolor(r, g ,g))?;
+            write!(f, "{}  ", l.to_string().on_truecolor(r, g ,g))?;
             g = 151 - g1 as u8;
             g1 /= 2;
         }
         for s in &self.span {
             write!(f, "{}  ",s)?;
         }
-        write!(f, "\n")?;
+        writeln!(f)?;
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use scraper::Html;
+use tracing::info;
+
+use crate::core::model::{Book, Chapter, ImageRef, Volume};
+use crate::core::site::SiteDefinition;
+use crate::core::source::Source;
+use crate::utils::html::{
+    extract_chapter, select_all_text, select_attr, select_links, select_text,
+};
+use crate::utils::fetch::Fetcher;
+use crate::utils::fetch::images::download_missing_images;
+
+/// 通用来源，按网站定义中的选择器通过http解析页面
+pub struct GenericSource {
+    url: String,
+    site: Arc<SiteDefinition>,
+    fetcher: Fetcher,
+    volumes: Vec<Volume>,
+}
+
+impl GenericSource {
+    pub fn new(url: String, site: Arc<SiteDefinition>) -> Result<Self> {
+        let fetcher = site.fetcher()?;
+        Ok(Self::with_fetcher(url, site, fetcher))
+    }
+
+    /// 使用指定的下载后端，例如回放录制的页面
+    pub fn with_fetcher(url: String, site: Arc<SiteDefinition>, fetcher: Fetcher) -> Self {
+        Self {
+            url,
+            site,
+            fetcher,
+            volumes: Vec::new(),
+        }
+    }
+
+    /// 获取页面内容
+    async fn fetch_html(&self, url: &str) -> Result<String> {
+        self.site.fetch_html(&*self.fetcher, url).await
+    }
+}
+
+#[async_trait]
+impl Source for GenericSource {
+    fn name(&self) -> &str {
+        self.site.key.as_str()
+    }
+
+    async fn fetch_book_info(&mut self) -> Result<Book> {
+        let body = self.fetch_html(&self.url).await?;
+        let selectors = &self.site.selectors;
+        let id = self.site.book_id(&self.url).unwrap_or_default();
+
+        let book = {
+            let html = Html::parse_document(&body);
+            let title = select_text(&html, &selectors.title)?;
+            let mut book = Book::new(&self.site.key, &id, &self.url, &title);
+            if !selectors.author.is_empty() {
+                book.metadata.author = select_text(&html, &selectors.author)?;
+            }
+            if !selectors.description.is_empty() {
+                book.metadata.description = select_text(&html, &selectors.description)?;
+            }
+            if !selectors.tags.is_empty() {
+                book.metadata.tags = select_all_text(&html, &selectors.tags)?;
+            }
+            if !selectors.illustrator.is_empty() {
+                let illustrator = select_text(&html, &selectors.illustrator)?;
+                book.metadata.illustrator = (!illustrator.is_empty()).then_some(illustrator);
+            }
+            if !selectors.publisher.is_empty() {
+                let publisher = select_text(&html, &selectors.publisher)?;
+                book.metadata.publisher = (!publisher.is_empty()).then_some(publisher);
+            }
+
+            let volumes = if selectors.volumes.is_empty() {
+                // 没有分卷的网站，整本书作为一卷
+                vec![Volume::new(&self.url, &title)]
+            } else {
+                self.links_to_volumes(&html, &selectors.volumes, &self.url)?
+            };
+            self.volumes = volumes;
+            book
+        };
+
+        if self.volumes.is_empty()
+            && !selectors.catalog_volumes.is_empty()
+            && let Some(catalog) = self.site.catalog_url(&id)
+        {
+            info!("书籍页没有卷列表，从目录页获取：{}", catalog);
+            let body = self.fetch_html(&catalog).await?;
+            let html = Html::parse_document(&body);
+            self.volumes = self.links_to_volumes(&html, &selectors.catalog_volumes, &catalog)?;
+        }
+        Ok(book)
+    }
+
+    async fn list_volumes(&mut self, _book: &Book) -> Result<Vec<Volume>> {
+        Ok(self.volumes.clone())
+    }
+
+    async fn list_chapters(&self, volume: &mut Volume) -> Result<()> {
+        let body = self.fetch_html(&volume.url).await?;
+        let selectors = &self.site.selectors;
+        {
+            let html = Html::parse_document(&body);
+            let cover = if selectors.cover.is_empty() {
+                String::new()
+            } else {
+                select_attr(&html, &selectors.cover, &selectors.cover_attr)?
+            };
+            volume.cover = (!cover.is_empty()).then(|| ImageRef::new(&cover));
+            volume.chapters = select_links(
+                &html,
+                &selectors.chapters,
+                &selectors.chapter_link,
+                None,
+                &volume.url,
+            )?
+            .into_iter()
+            .map(|(url, title)| Chapter::new(&url, &title))
+            .collect();
+        }
+        if volume.chapters.is_empty() {
+            return Err(anyhow!("没有找到章节：{}", volume.url));
+        }
+        if let Some(cover) = &volume.cover {
+            download_missing_images(&*self.fetcher, std::slice::from_ref(cover)).await;
+        }
+        Ok(())
+    }
+
+    async fn fetch_chapter(&self, chapter: &Chapter) -> Result<Chapter> {
+        let body = self.fetch_html(&chapter.url).await?;
+        let selectors = &self.site.selectors;
+        let (images, content) = extract_chapter(
+            &body,
+            &selectors.content,
+            &selectors.image_attr,
+            selectors.remove.iter().map(String::as_str).collect(),
+        )?;
+        let images: Vec<ImageRef> = images.into_iter().map(ImageRef::from).collect();
+        download_missing_images(&*self.fetcher, &images).await;
+        Ok(Chapter {
+            pages: vec![content],
+            images,
+            ..Chapter::new(&chapter.url, &chapter.title)
+        })
+    }
+}
+
+impl GenericSource {
+    /// 卷列表容器中的链接转换为卷
+    fn links_to_volumes(
+        &self,
+        html: &Html,
+        container: &str,
+        base_url: &str,
+    ) -> Result<Vec<Volume>> {
+        let selectors = &self.site.selectors;
+        let name_attr =
+            (!selectors.volume_name_attr.is_empty()).then_some(selectors.volume_name_attr.as_str());
+        Ok(
+            select_links(html, container, &selectors.volume_link, name_attr, base_url)?
+                .into_iter()
+                .map(|(url, name)| Volume::new(&url, &name))
+                .collect(),
+        )
+    }
+}
@@ -1 +1 @@
-pub mod bilinovel;
\ No newline at end of file
+pub mod bilinovel;pub mod generic;
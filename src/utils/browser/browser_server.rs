@@ -119,7 +119,7 @@ impl BrowserConfig {
     }
 }
 
-/// 浏览器标签信息
+// 浏览器标签信息
 // #[derive(Debug, Deserialize)]
 // pub struct BrowserTab {
 //     pub id: String,
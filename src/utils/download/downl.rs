@@ -24,7 +24,9 @@ pub mod down {
         cmd.arg(url)
             .args(&args)
             .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped());
+            .stderr(std::process::Stdio::piped())
+            // 下载被中止时结束single-file进程
+            .kill_on_drop(true);
 
         // 启动进程
         let mut child = cmd
@@ -115,11 +117,11 @@ pub mod down {
         }
 
         // 如果有任何解析错误，打印原始内容
-        if has_errors {
-            if let Ok(output) = String::from_utf8(buffer) {
-                error!("JSON解析错误,即将打印原始内容");
-                eprintln!("原始输出:\n{}", output);
-            }
+        if has_errors
+            && let Ok(output) = String::from_utf8(buffer)
+        {
+            error!("JSON解析错误,即将打印原始内容");
+            eprintln!("原始输出:\n{}", output);
         }
 
         Ok(results)
@@ -69,6 +69,16 @@ pub const DEFAULT_CSS: &str = r#"
         border-radius: 5px;
         box-shadow: 0 4px 10px rgba(0,0,0,0.15);
     }
+    .toc-list .toc-list {
+        padding-left: 1.5em;
+    }
+    .volume-title {
+        text-align: center;
+        margin-top: 3em;
+    }
+    .volume-cover {
+        max-width: 70%;
+    }
     @media (prefers-color-scheme: dark) {
         body {
             color: #ddd;
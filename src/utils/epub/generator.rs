@@ -0,0 +1,662 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::Cursor,
+};
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use epub_builder::{EpubBuilder, EpubContent, MetadataOpf, ReferenceType, ZipLibrary};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use tracing::error;
+use uuid::Uuid;
+use std::io::{Seek, Write};
+
+use super::{default_css, opf, xhtml::clean_html};
+use crate::{
+    core::model::{Book, Chapter, ImageRef, Volume},
+    utils::httpserver::ImageData,
+};
+
+/// 写入epub的生成程序
+const GENERATOR: &str = concat!("novel-packer ", env!("CARGO_PKG_VERSION"));
+
+/// 章节中引用图片的属性，提取章节时写为 `images/{文件名}`
+static IMAGE_SRC: Lazy<Regex> = Lazy::new(|| Regex::new(r#"src="images/([^"]*)""#).unwrap());
+
+/// 图片链接对应的epub中的路径，相对于章节所在的目录
+type ImageNames = HashMap<String, String>;
+
+/// 全书封面的路径
+const COVER_PATH: &str = "cover.png";
+
+///将一卷或多卷打包为epub，只使用通用的书籍模型
+pub struct EpubGenerator<'a> {
+    title: String,
+    volumes: Vec<&'a Volume>,
+    /// 合集，目录分为卷和章节两级，每卷前有卷的标题页
+    omnibus: bool,
+    /// 所属的书籍，用于生成标识符
+    book: Option<&'a Book>,
+    images: &'a HashMap<String, ImageData>,
+    css: Option<String>,
+}
+
+impl<'a> EpubGenerator<'a> {
+    pub fn new(volume: &'a Volume, images: &'a HashMap<String, ImageData>) -> Self {
+        EpubGenerator {
+            title: volume.name.clone(),
+            volumes: vec![volume],
+            omnibus: false,
+            book: None,
+            images,
+            css: None,
+        }
+    }
+
+    /// 将多卷合并为一本书，元数据和封面使用第一卷的
+    pub fn omnibus(
+        title: &str,
+        volumes: Vec<&'a Volume>,
+        images: &'a HashMap<String, ImageData>,
+    ) -> Self {
+        EpubGenerator {
+            title: title.to_string(),
+            volumes,
+            omnibus: true,
+            book: None,
+            images,
+            css: None,
+        }
+    }
+
+    /// 设置所属的书籍，标识符由来源网站、书籍号和卷号生成
+    pub fn with_book(mut self, book: &'a Book) -> Self {
+        self.book = Some(book);
+        self
+    }
+
+    // 添加设置自定义CSS的方法
+    pub fn with_css(mut self, path: &str) -> Self {
+        let css = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("设置css出错：{}", e);
+            error!("设置css出错：{}", e);
+            default_css::DEFAULT_CSS.to_string()
+        });
+        self.css = Some(css);
+        self
+    }
+
+    pub fn generate_epub<W: Write + Seek>(&self, output: W) -> Result<()> {
+        if self.volumes.is_empty() {
+            return Err(anyhow!("没有要打包的卷"));
+        }
+        let mut builder = EpubBuilder::new(ZipLibrary::new()?)?;
+        builder.epub_version(epub_builder::EpubVersion::V30);
+
+        // 设置元数据
+        self.set_metadata(&mut builder)?;
+
+        // 添加CSS样式表
+        self.add_stylesheet(&mut builder)?;
+
+        // 添加封面图片
+        self.add_cover_image(&mut builder)?;
+
+        // 添加所有章节中引用的图片资源
+        let names = self.image_names();
+        self.add_chapter_images(&mut builder, &names)?;
+
+        // 添加封面页面
+        self.add_cover_page(&mut builder)?;
+
+        // 添加目录页面
+        self.add_table_of_contents(&mut builder)?;
+
+        // 添加章节内容
+        self.add_chapters(&mut builder, &names)?;
+
+        // epub-builder 不支持的元数据在生成后插入
+        let extra = self.extra_metadata();
+        if extra.is_empty() {
+            builder.generate(output)?;
+        } else {
+            let mut epub = Vec::new();
+            builder.generate(&mut epub)?;
+            opf::insert_metadata(&epub, &extra, output)?;
+        }
+        Ok(())
+    }
+
+    /// 需要在生成后插入 opf 的元数据
+    fn extra_metadata(&self) -> String {
+        let metadata = &self.first().metadata;
+        let mut extra = vec![opf::source_metadata(&self.source_url())];
+        if let Some(illustrator) = &metadata.illustrator {
+            extra.push(opf::illustrator_metadata(illustrator));
+        }
+        if let Some(publisher) = &metadata.publisher {
+            extra.push(opf::publisher_metadata(publisher));
+        }
+        if let Some(series) = &metadata.series {
+            extra.push(opf::series_metadata(series, metadata.series_index));
+        }
+        extra.join("\n")
+    }
+
+    /// 来源链接，合集为书籍的链接
+    fn source_url(&self) -> String {
+        match self.book {
+            Some(book) if self.omnibus && !book.url.is_empty() => book.url.clone(),
+            _ => self.first().url.clone(),
+        }
+    }
+
+    /// 由来源网站、书籍号和卷号得到的标识符，同一卷每次打包都相同
+    fn identifier(&self) -> Uuid {
+        let volumes: Vec<&str> = self
+            .volumes
+            .iter()
+            .map(|v| if v.id.is_empty() { v.url.as_str() } else { v.id.as_str() })
+            .collect();
+        let name = match self.book {
+            Some(book) => format!(
+                "novel-packer:{}/{}/{}",
+                book.source,
+                book.id,
+                volumes.join(",")
+            ),
+            None => format!("novel-packer:{}", volumes.join(",")),
+        };
+        Uuid::new_v5(&Uuid::NAMESPACE_URL, name.as_bytes())
+    }
+
+    /// 第一卷，元数据和封面来自这一卷
+    fn first(&self) -> &'a Volume {
+        self.volumes[0]
+    }
+
+    fn set_metadata(&self, builder: &mut EpubBuilder<ZipLibrary>) -> Result<()> {
+        let metadata = &self.first().metadata;
+        builder.metadata("title", &self.title)?;
+        builder.metadata("author", &metadata.author)?;
+        builder.metadata("lang", metadata.language())?;
+        builder.set_uuid(self.identifier());
+        builder.set_modified_date(Utc::now());
+        builder.set_generator(GENERATOR);
+        builder.add_metadata_opf(MetadataOpf {
+            name: "generator".to_string(),
+            content: GENERATOR.to_string(),
+        });
+
+        if !metadata.description.is_empty() {
+            builder.metadata("description", &metadata.description)?;
+        }
+
+        // calibre 的系列元数据
+        if let Some(series) = &metadata.series {
+            builder.add_metadata_opf(MetadataOpf {
+                name: "calibre:series".to_string(),
+                content: series.clone(),
+            });
+            if let Some(index) = metadata.series_index {
+                builder.add_metadata_opf(MetadataOpf {
+                    name: "calibre:series_index".to_string(),
+                    content: index.to_string(),
+                });
+            }
+        }
+
+        // 合集使用所有卷的标签
+        let mut tags = HashSet::new();
+        for tag in self.volumes.iter().flat_map(|v| &v.metadata.tags) {
+            if tags.insert(tag) {
+                builder.metadata("subject", tag)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_stylesheet(&self, builder: &mut EpubBuilder<ZipLibrary>) -> Result<()> {
+        // 使用自定义CSS或默认CSS
+        let css_content = match &self.css {
+            Some(custom_css) => custom_css.as_str(),
+            None => default_css::DEFAULT_CSS,
+        };
+
+        builder.add_resource("styles.css", Cursor::new(css_content), "text/css")?;
+        Ok(())
+    }
+
+    fn add_cover_image(&self, builder: &mut EpubBuilder<ZipLibrary>) -> Result<()> {
+        if let Some(image_data) = self
+            .first()
+            .cover
+            .as_ref()
+            .and_then(|cover| self.images.get(&cover.url))
+        {
+            // 使用 Cursor 包装字节数据，使其实现 Read trait
+            let reader = Cursor::new(&image_data.u8_data);
+            builder.add_cover_image(COVER_PATH, reader, &image_data.mime_type)?;
+        }
+        Ok(())
+    }
+
+    /// 引用的所有图片，合集中第二卷起的封面用于卷的标题页
+    fn referenced_images(&self) -> impl Iterator<Item = &'a ImageRef> {
+        let covers = self
+            .volumes
+            .iter()
+            .skip(1)
+            .filter(|_| self.omnibus)
+            .filter_map(|v| v.cover.as_ref());
+        let chapter_images = self
+            .volumes
+            .iter()
+            .flat_map(|v| &v.chapters)
+            .flat_map(|c| &c.images);
+        covers.chain(chapter_images)
+    }
+
+    /// 按链接给每张图片分配epub中的路径，章节中的封面使用全书封面，文件名相同的不同图片加上序号
+    fn image_names(&self) -> ImageNames {
+        let mut names = ImageNames::new();
+        if let Some(cover) = &self.first().cover
+            && self.images.contains_key(&cover.url)
+        {
+            names.insert(cover.url.clone(), COVER_PATH.to_string());
+        }
+        let mut used = HashSet::new();
+        for image in self.referenced_images() {
+            if names.contains_key(&image.url) {
+                continue;
+            }
+            if let Some(image_data) = self.images.get(&image.url) {
+                let name = unique_name(&image_data.filename, &mut used);
+                names.insert(image.url.clone(), format!("images/{}", name));
+            }
+        }
+        names
+    }
+
+    fn add_chapter_images(
+        &self,
+        builder: &mut EpubBuilder<ZipLibrary>,
+        names: &ImageNames,
+    ) -> Result<()> {
+        let mut added_images = HashSet::new(); // 用于跟踪已添加的图片
+
+        for image in self.referenced_images() {
+            // 跳过封面图片，因为它已经单独添加了
+            if self.first().cover.as_ref() == Some(image) {
+                continue;
+            }
+
+            // 多卷共用的图片只添加一次
+            if !added_images.insert(&image.url) {
+                continue;
+            }
+            if let (Some(image_data), Some(path)) =
+                (self.images.get(&image.url), names.get(&image.url))
+            {
+                // 使用 Cursor 包装字节数据，使其实现 Read trait
+                let reader = Cursor::new(&image_data.u8_data);
+                builder.add_resource(path, reader, &image_data.mime_type)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_cover_page(&self, builder: &mut EpubBuilder<ZipLibrary>) -> Result<()> {
+        let cover_content = r#"<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <title>封面</title>
+    <link rel="stylesheet" type="text/css" href="styles.css" />
+</head>
+<body>
+    <img lass="cover-image" src="cover.png" alt="封面图片" />
+</body>
+</html>"#;
+
+        builder.add_content(
+            EpubContent::new("cover.xhtml", cover_content.as_bytes())
+                .title("封面")
+                .reftype(ReferenceType::Cover),
+        )?;
+        Ok(())
+    }
+
+    fn add_table_of_contents(&self, builder: &mut EpubBuilder<ZipLibrary>) -> Result<()> {
+        let mut toc_content = String::new();
+        toc_content.push_str(
+            r#"<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <title>目录</title>
+    <link rel="stylesheet" type="text/css" href="styles.css" />
+</head>
+<body>
+    <h1 class="toc-title">目录</h1>
+    <ul class="toc-list">"#,
+        );
+
+        for (v, volume) in self.volumes.iter().enumerate() {
+            if self.omnibus {
+                toc_content.push_str(&format!(
+                    r#"<li class="toc-item"><a class="toc-link" href="{}">{}</a><ul class="toc-list">"#,
+                    volume_filename(v),
+                    escape_xml(&volume.name)
+                ));
+            }
+            for (index, chapter) in self.volume_chapters(v) {
+                toc_content.push_str(&format!(
+                    r#"<li class="toc-item"><a class="toc-link" href="{}">{}</a></li>"#,
+                    chapter_filename(index),
+                    escape_xml(&chapter.title)
+                ));
+            }
+            if self.omnibus {
+                toc_content.push_str("</ul></li>");
+            }
+        }
+
+        toc_content.push_str(
+            r#"</ul>
+</body>
+</html>"#,
+        );
+
+        builder.add_content(
+            EpubContent::new("toc.xhtml", toc_content.as_bytes())
+                .title("目录")
+                .reftype(ReferenceType::Toc),
+        )?;
+        Ok(())
+    }
+
+    /// 一卷的章节和它们在全书中的序号
+    fn volume_chapters(&self, v: usize) -> impl Iterator<Item = (usize, &'a Chapter)> {
+        let offset: usize = self.volumes[..v].iter().map(|v| v.chapters.len()).sum();
+        self.volumes[v]
+            .chapters
+            .iter()
+            .enumerate()
+            .map(move |(i, chapter)| (offset + i, chapter))
+    }
+
+    fn add_chapters(
+        &self,
+        builder: &mut EpubBuilder<ZipLibrary>,
+        names: &ImageNames,
+    ) -> Result<()> {
+        // 合集中章节在目录的第二级
+        let level = if self.omnibus { 2 } else { 1 };
+        for v in 0..self.volumes.len() {
+            if self.omnibus {
+                self.add_volume_page(builder, v, names)?;
+            }
+            for (index, chapter) in self.volume_chapters(v) {
+                let filename = chapter_filename(index);
+                let title = &chapter.title;
+
+                // 构建完整的 XHTML 文档
+                let content = self.build_chapter_content(chapter, names)?;
+
+                let mut epub_content = EpubContent::new(&filename, content.as_bytes())
+                    .title(title)
+                    .level(level);
+
+                // 只有第一章标记为文本开始
+                if index == 0 && !self.omnibus {
+                    epub_content = epub_content.reftype(ReferenceType::Text);
+                }
+
+                builder.add_content(epub_content)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 合集中卷的标题页，有卷的封面时显示封面
+    fn add_volume_page(
+        &self,
+        builder: &mut EpubBuilder<ZipLibrary>,
+        v: usize,
+        names: &ImageNames,
+    ) -> Result<()> {
+        let volume = self.volumes[v];
+        // 第一卷的封面就是全书的封面
+        let cover = volume
+            .cover
+            .as_ref()
+            .and_then(|cover| names.get(&cover.url))
+            .map(|src| {
+                format!(
+                    r#"<img class="volume-cover" src="{}" alt="{}" />"#,
+                    src,
+                    escape_xml(&volume.name)
+                )
+            })
+            .unwrap_or_default();
+        let content = format!(
+            r#"<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <title>{}</title>
+    <link rel="stylesheet" type="text/css" href="styles.css" />
+</head>
+<body>
+    <div class="volume-title">
+    {}
+    <h1>{}</h1>
+    </div>
+</body>
+</html>"#,
+            escape_xml(&volume.name),
+            cover,
+            escape_xml(&volume.name)
+        );
+
+        let mut epub_content =
+            EpubContent::new(volume_filename(v), content.as_bytes()).title(&volume.name);
+        if v == 0 {
+            epub_content = epub_content.reftype(ReferenceType::Text);
+        }
+        builder.add_content(epub_content)?;
+        Ok(())
+    }
+
+    fn build_chapter_content(&self, chapter: &Chapter, names: &ImageNames) -> Result<String> {
+        // 构建完整的 XHTML 文档
+        Ok(format!(
+            r#"<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <title>{}</title>
+    <meta charset="UTF-8" />
+    <link rel="stylesheet" type="text/css" href="styles.css" />
+</head>
+<body>
+    <h1>{}</h1>
+    {}
+</body>
+</html>"#,
+            escape_xml(&chapter.title),
+            escape_xml(&chapter.title),
+            clean_html(&self.rename_images(chapter, names))?
+        ))
+    }
+
+    /// 章节中图片的引用改为epub中的路径，同名的引用按图片的顺序对应
+    fn rename_images(&self, chapter: &Chapter, names: &ImageNames) -> String {
+        let html = chapter.pages.join("");
+        let mut renamed: HashMap<&str, VecDeque<&str>> = HashMap::new();
+        for image in &chapter.images {
+            if let (Some(image_data), Some(path)) =
+                (self.images.get(&image.url), names.get(&image.url))
+            {
+                renamed
+                    .entry(image_data.filename.as_str())
+                    .or_default()
+                    .push_back(path.as_str());
+            }
+        }
+        if renamed.iter().all(|(filename, paths)| {
+            paths
+                .iter()
+                .all(|p| p.strip_prefix("images/") == Some(filename))
+        }) {
+            return html;
+        }
+        IMAGE_SRC
+            .replace_all(&html, |caps: &Captures| {
+                let path = match renamed.get_mut(&caps[1]) {
+                    // 最后一个留给之后重复的引用
+                    Some(paths) if paths.len() > 1 => paths.pop_front(),
+                    Some(paths) => paths.front().copied(),
+                    None => None,
+                };
+                match path {
+                    Some(path) => format!(r#"src="{}""#, path),
+                    None => caps[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+/// 没有用过的文件名，重名时在扩展名前加上序号
+fn unique_name(filename: &str, used: &mut HashSet<String>) -> String {
+    let (stem, ext) = match filename.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (filename, String::new()),
+    };
+    let mut name = filename.to_string();
+    let mut n = 1;
+    while !used.insert(name.clone()) {
+        n += 1;
+        name = format!("{}-{}{}", stem, n, ext);
+    }
+    name
+}
+
+fn chapter_filename(index: usize) -> String {
+    format!("chapter_{:03}.xhtml", index + 1)
+}
+
+fn volume_filename(v: usize) -> String {
+    format!("volume_{:02}.xhtml", v + 1)
+}
+
+// XML 转义函数
+pub(super) fn escape_xml(s: &str) -> String {
+    s.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+        .replace("'", "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier() {
+        let images = HashMap::new();
+        let book = Book::new(
+            "bilinovel",
+            "2356",
+            "https://www.linovelib.com/novel/2356.html",
+            "书",
+        );
+        let mut volume = Volume::new("https://www.linovelib.com/novel/2356/vol_1.html", "第一卷");
+        volume.id = "1".to_string();
+        let mut other = volume.clone();
+        other.id = "2".to_string();
+
+        let id = EpubGenerator::new(&volume, &images)
+            .with_book(&book)
+            .identifier();
+        assert_eq!(id.get_version_num(), 5);
+        assert_eq!(
+            id,
+            EpubGenerator::new(&volume, &images)
+                .with_book(&book)
+                .identifier()
+        );
+        assert_ne!(
+            id,
+            EpubGenerator::new(&other, &images)
+                .with_book(&book)
+                .identifier()
+        );
+        assert_ne!(
+            id,
+            EpubGenerator::omnibus("书", vec![&volume, &other], &images)
+                .with_book(&book)
+                .identifier()
+        );
+    }
+
+    #[test]
+    fn test_image_names() {
+        let mut images = HashMap::new();
+        for url in ["https://a.com/1/1.jpg", "https://a.com/2/1.jpg"] {
+            images.insert(
+                url.to_string(),
+                ImageData::new("1.jpg", "image/jpeg", Vec::new()),
+            );
+        }
+        let mut volume = Volume::new("https://a.com/vol_1.html", "第一卷");
+        let mut chapter = Chapter::new("https://a.com/1.html", "第一章");
+        chapter.pages = vec![r#"<img src="images/1.jpg"><img src="images/1.jpg">"#.to_string()];
+        chapter.images = vec![
+            ImageRef::from("https://a.com/1/1.jpg".to_string()),
+            ImageRef::from("https://a.com/2/1.jpg".to_string()),
+        ];
+        volume.chapters.push(chapter);
+
+        let generator = EpubGenerator::new(&volume, &images);
+        let names = generator.image_names();
+        assert_eq!(names["https://a.com/1/1.jpg"], "images/1.jpg");
+        assert_eq!(names["https://a.com/2/1.jpg"], "images/1-2.jpg");
+        assert_eq!(
+            generator.rename_images(&volume.chapters[0], &names),
+            r#"<img src="images/1.jpg"><img src="images/1-2.jpg">"#
+        );
+    }
+
+    #[test]
+    fn test_cover_in_chapter() {
+        let cover_url = "https://a.com/cover.jpg".to_string();
+        let images = HashMap::from([(
+            cover_url.clone(),
+            ImageData::new("cover.jpg", "image/jpeg", vec![0xff, 0xd8, 0xff]),
+        )]);
+        let mut volume = Volume::new("https://a.com/vol_1.html", "第一卷");
+        volume.cover = Some(ImageRef::from(cover_url.clone()));
+        let mut chapter = Chapter::new("https://a.com/1.html", "插图");
+        chapter.pages = vec![r#"<img src="images/cover.jpg">"#.to_string()];
+        chapter.images = vec![ImageRef::from(cover_url)];
+        volume.chapters.push(chapter);
+
+        // 章节中的封面引用全书封面，不会指向清单中没有的文件
+        let generator = EpubGenerator::new(&volume, &images);
+        let names = generator.image_names();
+        assert_eq!(
+            generator.rename_images(&volume.chapters[0], &names),
+            r#"<img src="cover.png">"#
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.epub");
+        generator
+            .generate_epub(std::fs::File::create(&path).unwrap())
+            .unwrap();
+        let report = super::super::validate::validate_epub(&path).unwrap();
+        assert!(report.is_ok(), "{}", report);
+    }
+}
@@ -0,0 +1,111 @@
+//! 修改生成的epub中的 `content.opf`
+//!
+//! epub-builder 只能写入 `<meta name="" content="">`，EPUB3 中带 `property` 的元数据在生成后插入。
+
+use std::io::{Cursor, Read, Seek, Write};
+
+use anyhow::Result;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use super::generator::escape_xml;
+
+/// epub-builder 生成的 opf 文件的位置
+const OPF_PATH: &str = "OEBPS/content.opf";
+
+/// 来源链接
+pub fn source_metadata(url: &str) -> String {
+    format!("<dc:source>{}</dc:source>", escape_xml(url))
+}
+
+/// 插画作者，作为贡献者而不是作者
+pub fn illustrator_metadata(illustrator: &str) -> String {
+    format!(
+        r##"<dc:contributor id="illustrator">{}</dc:contributor>
+<meta refines="#illustrator" property="role" scheme="marc:relators">ill</meta>"##,
+        escape_xml(illustrator)
+    )
+}
+
+/// 出版社或文库
+pub fn publisher_metadata(publisher: &str) -> String {
+    format!("<dc:publisher>{}</dc:publisher>", escape_xml(publisher))
+}
+
+/// EPUB3 的系列元数据，Apple Books 和 KOReader 用它把同一系列的书放在一起
+pub fn series_metadata(series: &str, index: Option<u32>) -> String {
+    let mut metadata = format!(
+        r##"<meta property="belongs-to-collection" id="series">{}</meta>
+<meta refines="#series" property="collection-type">series</meta>"##,
+        escape_xml(series)
+    );
+    if let Some(index) = index {
+        metadata.push_str(&format!(
+            "\n<meta refines=\"#series\" property=\"group-position\">{}</meta>",
+            index
+        ));
+    }
+    metadata
+}
+
+/// 在 opf 的 `</metadata>` 前插入元数据，其他文件原样复制
+pub fn insert_metadata<W: Write + Seek>(epub: &[u8], metadata: &str, output: W) -> Result<()> {
+    let mut archive = ZipArchive::new(Cursor::new(epub))?;
+    let mut writer = ZipWriter::new(output);
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.name() != OPF_PATH {
+            // 保持原来的压缩方式，mimetype 仍然是第一个不压缩的文件
+            writer.raw_copy_file(file)?;
+            continue;
+        }
+        let mut opf = String::new();
+        file.read_to_string(&mut opf)?;
+        // 和原来的元数据保持相同的缩进
+        let inserted: String = metadata.lines().map(|l| format!("  {}\n  ", l)).collect();
+        let opf = opf.replacen("</metadata>", &format!("{}</metadata>", inserted), 1);
+        let options = SimpleFileOptions::default().compression_method(file.compression());
+        writer.start_file(OPF_PATH, options)?;
+        writer.write_all(opf.as_bytes())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_metadata() {
+        let mut epub = Vec::new();
+        {
+            let mut writer = ZipWriter::new(Cursor::new(&mut epub));
+            let stored =
+                SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+            writer.start_file("mimetype", stored).unwrap();
+            writer.write_all(b"application/epub+zip").unwrap();
+            writer
+                .start_file(OPF_PATH, SimpleFileOptions::default())
+                .unwrap();
+            writer
+                .write_all(b"<package><metadata>\n  </metadata></package>")
+                .unwrap();
+            writer.finish().unwrap();
+        }
+
+        let mut output = Cursor::new(Vec::new());
+        insert_metadata(&epub, &series_metadata("书 & 名", Some(2)), &mut output).unwrap();
+        let mut archive = ZipArchive::new(output).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        let mut opf = String::new();
+        archive
+            .by_name(OPF_PATH)
+            .unwrap()
+            .read_to_string(&mut opf)
+            .unwrap();
+        assert!(opf.contains(r#"id="series">书 &amp; 名</meta>"#));
+        assert!(opf.contains(r#"property="group-position">2</meta>"#));
+        assert!(opf.ends_with("</metadata></package>"));
+    }
+}
@@ -0,0 +1,525 @@
+//! 离线校验生成的epub
+//!
+//! 只做 epubcheck 中最常见的几项检查：mimetype、opf 的清单和书脊、XHTML 是否格式正确、
+//! 图片等资源是否在清单中以及重复的 id，足够发现会让阅读器打不开的问题。
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use zip::ZipArchive;
+
+const MIMETYPE: &str = "application/epub+zip";
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+const XHTML_TYPE: &str = "application/xhtml+xml";
+
+/// 问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// 阅读器可能无法打开
+    Error,
+    /// 不影响阅读，但不符合规范
+    Warning,
+}
+
+/// 校验发现的问题
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// 出问题的文件在epub中的路径，整个文件的问题为空
+    pub file: String,
+    pub message: String,
+}
+
+/// 一个epub的校验结果
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub path: PathBuf,
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == Severity::Warning)
+    }
+
+    /// 没有错误，可以有警告
+    pub fn is_ok(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    fn error(&mut self, file: &str, message: impl Into<String>) {
+        self.push(Severity::Error, file, message);
+    }
+
+    fn warning(&mut self, file: &str, message: impl Into<String>) {
+        self.push(Severity::Warning, file, message);
+    }
+
+    fn push(&mut self, severity: Severity, file: &str, message: impl Into<String>) {
+        self.issues.push(Issue {
+            severity,
+            file: file.to_string(),
+            message: message.into(),
+        });
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}：{}个错误，{}个警告",
+            self.path.display(),
+            self.errors().count(),
+            self.warnings().count()
+        )?;
+        for issue in self.errors().chain(self.warnings()) {
+            let severity = match issue.severity {
+                Severity::Error => "错误",
+                Severity::Warning => "警告",
+            };
+            if issue.file.is_empty() {
+                write!(f, "\n  {} {}", severity, issue.message)?;
+            } else {
+                write!(f, "\n  {} [{}] {}", severity, issue.file, issue.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 清单中的一项
+struct ManifestItem {
+    id: String,
+    /// 在epub中的完整路径
+    path: String,
+    media_type: String,
+}
+
+/// 校验epub文件，文件无法读取时返回错误，其他问题都记录在结果中
+pub fn validate_epub<P: AsRef<Path>>(path: P) -> Result<ValidationReport> {
+    let path = path.as_ref();
+    let file =
+        std::fs::File::open(path).with_context(|| format!("打开文件失败：{}", path.display()))?;
+    let mut report = validate_reader(file);
+    report.path = path.to_path_buf();
+    Ok(report)
+}
+
+/// 校验epub的内容
+pub fn validate_reader<R: Read + Seek>(reader: R) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let mut archive = match ZipArchive::new(reader) {
+        Ok(archive) => archive,
+        Err(e) => {
+            report.error("", format!("不是有效的zip文件：{}", e));
+            return report;
+        }
+    };
+
+    check_mimetype(&mut archive, &mut report);
+    let Some(opf_path) = read_text(&mut archive, CONTAINER_PATH, &mut report)
+        .and_then(|container| find_rootfile(&container, &mut report))
+    else {
+        return report;
+    };
+    let Some(opf) = read_text(&mut archive, &opf_path, &mut report) else {
+        return report;
+    };
+    let Some((manifest, spine)) = parse_opf(&opf, &opf_path, &mut report) else {
+        return report;
+    };
+
+    let files: HashSet<String> = archive.file_names().map(str::to_string).collect();
+    check_manifest(&manifest, &spine, &files, &opf_path, &mut report);
+
+    let manifest_paths: HashSet<&str> = manifest.iter().map(|i| i.path.as_str()).collect();
+    for item in manifest.iter().filter(|i| i.media_type == XHTML_TYPE) {
+        if !files.contains(&item.path) {
+            continue;
+        }
+        if let Some(content) = read_text(&mut archive, &item.path, &mut report) {
+            check_xhtml(&content, &item.path, &manifest_paths, &mut report);
+        }
+    }
+    report
+}
+
+/// mimetype 必须是第一个文件，不压缩，内容为 `application/epub+zip`
+fn check_mimetype<R: Read + Seek>(archive: &mut ZipArchive<R>, report: &mut ValidationReport) {
+    let mut first = match archive.by_index(0) {
+        Ok(file) => file,
+        Err(_) => {
+            report.error("", "epub中没有文件");
+            return;
+        }
+    };
+    if first.name() != "mimetype" {
+        report.error("mimetype", "mimetype 不是第一个文件");
+        return;
+    }
+    if first.compression() != zip::CompressionMethod::Stored {
+        report.error("mimetype", "mimetype 不能压缩");
+    }
+    let mut content = String::new();
+    if first.read_to_string(&mut content).is_err() || content != MIMETYPE {
+        report.error("mimetype", format!("内容应为 {}", MIMETYPE));
+    }
+}
+
+/// 读取UTF-8文本文件，失败时记录错误
+fn read_text<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+    report: &mut ValidationReport,
+) -> Option<String> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(_) => {
+            report.error(name, "文件不存在");
+            return None;
+        }
+    };
+    let mut content = Vec::new();
+    if let Err(e) = file.read_to_end(&mut content) {
+        report.error(name, format!("读取失败：{}", e));
+        return None;
+    }
+    match String::from_utf8(content) {
+        Ok(content) => Some(content),
+        Err(_) => {
+            report.error(name, "不是UTF-8编码");
+            None
+        }
+    }
+}
+
+/// 从 container.xml 中找到 opf 的路径
+fn find_rootfile(container: &str, report: &mut ValidationReport) -> Option<String> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e) | Event::Empty(e)) if e.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = attribute(&e, "full-path") {
+                    return Some(path);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                report.error(CONTAINER_PATH, format!("XML格式错误：{}", e));
+                return None;
+            }
+            _ => {}
+        }
+    }
+    report.error(CONTAINER_PATH, "没有找到 rootfile");
+    None
+}
+
+/// 解析 opf 的清单和书脊
+fn parse_opf(
+    opf: &str,
+    opf_path: &str,
+    report: &mut ValidationReport,
+) -> Option<(Vec<ManifestItem>, Vec<String>)> {
+    let mut manifest = Vec::new();
+    let mut spine = Vec::new();
+    let mut reader = Reader::from_str(opf);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e) | Event::Empty(e)) => match e.local_name().as_ref() {
+                b"item" => {
+                    let href = attribute(&e, "href").unwrap_or_default();
+                    manifest.push(ManifestItem {
+                        id: attribute(&e, "id").unwrap_or_default(),
+                        path: resolve(opf_path, &href),
+                        media_type: attribute(&e, "media-type").unwrap_or_default(),
+                    });
+                }
+                b"itemref" => spine.push(attribute(&e, "idref").unwrap_or_default()),
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                report.error(opf_path, format!("XML格式错误：{}", e));
+                return None;
+            }
+            _ => {}
+        }
+    }
+    Some((manifest, spine))
+}
+
+/// 清单中的文件都存在、id 不重复，书脊引用清单中的项，其他文件都在清单中
+fn check_manifest(
+    manifest: &[ManifestItem],
+    spine: &[String],
+    files: &HashSet<String>,
+    opf_path: &str,
+    report: &mut ValidationReport,
+) {
+    let mut ids = HashMap::new();
+    for item in manifest {
+        if item.id.is_empty() {
+            report.error(opf_path, format!("清单中的 {} 没有 id", item.path));
+        } else if ids.insert(item.id.as_str(), item).is_some() {
+            report.error(opf_path, format!("清单中的 id 重复：{}", item.id));
+        }
+        if item.media_type.is_empty() {
+            report.error(opf_path, format!("清单中的 {} 没有 media-type", item.path));
+        }
+        if !files.contains(&item.path) {
+            report.error(opf_path, format!("清单中的文件不存在：{}", item.path));
+        }
+    }
+
+    if spine.is_empty() {
+        report.error(opf_path, "书脊为空");
+    }
+    for idref in spine {
+        match ids.get(idref.as_str()) {
+            None => report.error(opf_path, format!("书脊引用的 id 不在清单中：{}", idref)),
+            Some(item) if item.media_type != XHTML_TYPE => report.warning(
+                opf_path,
+                format!("书脊中的 {} 不是XHTML：{}", idref, item.media_type),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    let listed: HashSet<&str> = manifest.iter().map(|i| i.path.as_str()).collect();
+    let mut unlisted: Vec<&String> = files
+        .iter()
+        .filter(|f| !f.ends_with('/') && f.as_str() != "mimetype" && f.as_str() != opf_path)
+        .filter(|f| !f.starts_with("META-INF/") && !listed.contains(f.as_str()))
+        .collect();
+    unlisted.sort();
+    for file in unlisted {
+        report.warning(file, "文件不在清单中");
+    }
+}
+
+/// XHTML 格式正确、id 不重复，引用的图片和样式表都在清单中
+fn check_xhtml(content: &str, path: &str, manifest: &HashSet<&str>, report: &mut ValidationReport) {
+    let mut reader = Reader::from_str(content);
+    let mut open = Vec::new();
+    let mut ids = HashSet::new();
+    loop {
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(e) => {
+                report.error(
+                    path,
+                    format!("XML格式错误（位置 {}）：{}", reader.error_position(), e),
+                );
+                return;
+            }
+        };
+        match &event {
+            Event::Start(e) | Event::Empty(e) => {
+                if matches!(event, Event::Start(_)) {
+                    open.push(e.name().as_ref().to_vec());
+                }
+                for attr in e.attributes() {
+                    let attr = match attr {
+                        Ok(attr) => attr,
+                        Err(err) => {
+                            report.error(path, format!("属性格式错误：{}", err));
+                            continue;
+                        }
+                    };
+                    let value = match attr.unescape_value() {
+                        Ok(value) => value.to_string(),
+                        Err(err) => {
+                            report.error(path, format!("属性值中的实体无效：{}", err));
+                            continue;
+                        }
+                    };
+                    match (e.local_name().as_ref(), attr.key.as_ref()) {
+                        (_, b"id") if !ids.insert(value.clone()) => {
+                            report.error(path, format!("id 重复：{}", value));
+                        }
+                        (b"img", b"src") | (b"link", b"href") | (b"image", b"href") => {
+                            check_reference(&value, path, manifest, report);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::GeneralRef(e) => {
+                // XHTML中只有XML预定义的实体，&nbsp; 等html实体会让阅读器无法解析
+                let name = String::from_utf8_lossy(e).to_string();
+                let predefined = matches!(name.as_str(), "amp" | "lt" | "gt" | "quot" | "apos");
+                if !predefined && !e.is_char_ref() {
+                    report.error(path, format!("未定义的实体：&{};", name));
+                } else if e.resolve_char_ref().is_err() {
+                    report.error(path, format!("无效的字符引用：&{};", name));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if let Some(tag) = open.last() {
+        report.error(
+            path,
+            format!("标签没有闭合：<{}>", String::from_utf8_lossy(tag)),
+        );
+    }
+}
+
+/// 图片等资源的链接，外部链接和 data: 链接不检查
+fn check_reference(
+    link: &str,
+    path: &str,
+    manifest: &HashSet<&str>,
+    report: &mut ValidationReport,
+) {
+    if link.is_empty() {
+        report.error(path, "资源链接为空");
+        return;
+    }
+    if link.contains("://") || link.starts_with("data:") {
+        return;
+    }
+    let target = resolve(path, link);
+    if !manifest.contains(target.as_str()) {
+        report.error(path, format!("引用的资源不在清单中：{}", link));
+    }
+}
+
+/// 将相对于 `base` 文件的链接转换为epub中的完整路径
+fn resolve(base: &str, link: &str) -> String {
+    let link = link.split(['#', '?']).next().unwrap_or_default();
+    let link = percent_decode(link);
+    let mut parts: Vec<&str> = base.split('/').collect();
+    // 去掉文件名，只保留所在的目录
+    parts.pop();
+    for part in link.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = s
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// 读取属性值
+fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn epub(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("mimetype", stored).unwrap();
+        writer.write_all(MIMETYPE.as_bytes()).unwrap();
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let mut cursor = writer.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#;
+
+    const OPF: &str = r#"<?xml version="1.0"?>
+<package><manifest>
+<item id="c1" href="chapter_001.xhtml" media-type="application/xhtml+xml"/>
+<item id="img" href="images/1.png" media-type="image/png"/>
+<item id="missing" href="chapter_002.xhtml" media-type="application/xhtml+xml"/>
+</manifest><spine><itemref idref="c1"/><itemref idref="c9"/></spine></package>"#;
+
+    #[test]
+    fn test_validate() {
+        let chapter = r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+<p id="a">第一段&nbsp;</p><p id="a"><img src="images/1.png" alt=""/><img src="images/%E5%9B%BE.png" alt=""/></p>
+<div></body></html>"#;
+        let report = validate_reader(epub(&[
+            (CONTAINER_PATH, CONTAINER),
+            ("OEBPS/content.opf", OPF),
+            ("OEBPS/chapter_001.xhtml", chapter),
+            ("OEBPS/images/1.png", "png"),
+            ("OEBPS/extra.css", ""),
+        ]));
+        let messages: Vec<&str> = report.errors().map(|i| i.message.as_str()).collect();
+        assert!(messages.contains(&"清单中的文件不存在：OEBPS/chapter_002.xhtml"));
+        assert!(messages.contains(&"书脊引用的 id 不在清单中：c9"));
+        assert!(messages.contains(&"未定义的实体：&nbsp;"));
+        assert!(messages.contains(&"id 重复：a"));
+        assert!(messages.contains(&"引用的资源不在清单中：images/%E5%9B%BE.png"));
+        assert!(messages.iter().any(|m| m.starts_with("XML格式错误")));
+        assert_eq!(report.warnings().count(), 1);
+        assert!(!report.is_ok());
+
+        let report = validate_reader(Cursor::new(b"not a zip".to_vec()));
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            resolve("OEBPS/content.opf", "images/1.png"),
+            "OEBPS/images/1.png"
+        );
+        assert_eq!(
+            resolve("OEBPS/text/c1.xhtml", "../images/%E5%9B%BE.png#x"),
+            "OEBPS/images/图.png"
+        );
+    }
+}
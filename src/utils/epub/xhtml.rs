@@ -0,0 +1,148 @@
+//! 章节html转换为XHTML
+//!
+//! 用 scraper（html5ever）解析后重新输出：没有闭合的标签会被补全，实体被解码后重新转义，
+//! 只保留白名单中的标签和属性，脚本、事件和统计用的属性都会被删除。
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use scraper::{ElementRef, Html, Node};
+
+/// 保留的标签
+#[rustfmt::skip]
+const ALLOWED_TAGS: &[&str] = &[
+    "p", "br", "hr", "img", "div", "span", "a", "b", "i", "u", "s", "em", "strong", "small",
+    "big", "sub", "sup", "del", "ins", "mark", "q", "cite", "abbr", "ruby", "rt", "rp", "rb", "h1",
+    "h2", "h3", "h4", "h5", "h6", "ul", "ol", "li", "dl", "dt", "dd", "blockquote", "pre", "code",
+    "table", "thead", "tbody", "tfoot", "tr", "td", "th", "caption", "figure", "figcaption",
+    "section", "article", "aside", "header", "footer",
+];
+
+/// 连同内容一起删除的标签，其他不在白名单中的标签只保留内容
+#[rustfmt::skip]
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "iframe", "object", "embed", "form", "input", "button",
+    "select", "textarea", "template", "svg", "math", "head", "title", "meta", "link",
+];
+
+/// 没有内容的标签，输出为 `<br />` 的形式
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+
+/// 所有标签都保留的属性
+const GLOBAL_ATTRS: &[&str] = &["class", "id", "title", "lang", "dir"];
+
+/// 标签保留的属性
+fn allowed_attr(tag: &str, attr: &str) -> bool {
+    GLOBAL_ATTRS.contains(&attr)
+        || matches!(
+            (tag, attr),
+            ("img", "src" | "alt" | "width" | "height")
+                | ("a", "href")
+                | ("td" | "th", "colspan" | "rowspan")
+                | ("ol", "start")
+        )
+}
+
+/// 将章节的html转换为XHTML片段
+pub fn clean_html(html: &str) -> Result<String> {
+    let fragment = Html::parse_fragment(html);
+    let mut output = String::with_capacity(html.len());
+    write_children(&mut output, fragment.root_element())?;
+    Ok(output)
+}
+
+fn write_children(output: &mut String, element: ElementRef) -> std::fmt::Result {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => output.push_str(&escape_text(text)),
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    write_element(output, child)?;
+                }
+            }
+            // 注释、文档类型等不输出
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn write_element(output: &mut String, element: ElementRef) -> std::fmt::Result {
+    let tag = element.value().name();
+    if DROPPED_TAGS.contains(&tag) {
+        return Ok(());
+    }
+    if !ALLOWED_TAGS.contains(&tag) {
+        // 如 font、center、html、body 等，只保留内容
+        return write_children(output, element);
+    }
+
+    write!(output, "<{}", tag)?;
+    let mut has_alt = false;
+    for (name, value) in element.value().attrs() {
+        if !allowed_attr(tag, name) {
+            continue;
+        }
+        if name == "href"
+            && value
+                .trim_start()
+                .to_ascii_lowercase()
+                .starts_with("javascript:")
+        {
+            continue;
+        }
+        has_alt |= name == "alt";
+        write!(output, " {}=\"{}\"", name, escape_attr(value))?;
+    }
+    // XHTML中图片必须有 alt
+    if tag == "img" && !has_alt {
+        output.push_str(" alt=\"\"");
+    }
+
+    if VOID_TAGS.contains(&tag) {
+        output.push_str(" />");
+        return Ok(());
+    }
+    output.push('>');
+    write_children(output, element)?;
+    write!(output, "</{}>", tag)
+}
+
+/// 转义文本，并删除XML中不允许的控制字符
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_attr(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_html() {
+        let html = r#"<!DOCTYPE html><p class=txt onclick="track()">第一段&nbsp;A&B<br>
+<p>第二段<img src=images/1.jpg data-src="https://example.com/1.jpg"></p>
+<script>alert(1)</script><!-- 广告 --><font color=red>红字</font>
+<a href="javascript:void(0)">链接</a><p>1 < 2</p>"#;
+        assert_eq!(
+            clean_html(html).unwrap(),
+            "<p class=\"txt\">第一段\u{a0}A&amp;B<br />\n</p>\
+             <p>第二段<img src=\"images/1.jpg\" alt=\"\" /></p>\n\
+             红字\n<a>链接</a><p>1 &lt; 2</p>"
+        );
+    }
+}
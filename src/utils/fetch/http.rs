@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::utils::fetch::{FetchBackend, Page};
+use crate::utils::httpclient::http_async::AsyncHttpClient;
+
+#[async_trait]
+impl FetchBackend for AsyncHttpClient {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<Page> {
+        let response = self.get_once(url).await?;
+        Ok(Page::new(&response.url, response.status, response.body))
+    }
+
+    async fn fetch_bytes(&self, url: &str) -> Result<(Vec<u8>, Option<String>)> {
+        self.get_bytes(url).await
+    }
+
+    fn retries(&self) -> (u32, Duration) {
+        (self.config().max_retries, self.config().retry_delay)
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+
+use tracing::error;
+
+use crate::core::model::ImageRef;
+use crate::utils::fetch::FetchBackend;
+use crate::utils::html::{image_filename, image_mime_type};
+use crate::utils::httpserver::{ImageData, add_or_update_images, get_image_by_url};
+
+/// 下载图片存储中还没有的图片，返回下载失败的数量，失败的图片留给检查阶段重试
+pub async fn download_missing_images(fetcher: &dyn FetchBackend, images: &[ImageRef]) -> usize {
+    let mut downloaded = HashMap::new();
+    let mut failed = 0;
+    for image in images {
+        if matches!(get_image_by_url(&image.url), Ok(Some(_))) {
+            continue;
+        }
+        let filename = image_filename(&image.url);
+        if filename.is_empty() {
+            continue;
+        }
+        match fetcher.fetch_bytes(&image.url).await {
+            Ok((data, content_type)) => {
+                let mime_type = content_type
+                    .filter(|t| t.starts_with("image/"))
+                    .unwrap_or_else(|| image_mime_type(&filename).to_string());
+                downloaded.insert(
+                    image.url.clone(),
+                    ImageData::new(&filename, &mime_type, data),
+                );
+            }
+            Err(e) => {
+                error!("图片下载失败 url:{}\terror:{:#}", image.url, e);
+                failed += 1;
+            }
+        }
+    }
+    if !downloaded.is_empty() {
+        add_or_update_images(downloaded).unwrap_or_else(|e| error!("保存图片失败：{}", e));
+    }
+    failed
+}
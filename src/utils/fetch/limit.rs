@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::utils::fetch::{FetchBackend, Page};
+
+/// 每个主机的请求频率限制，在 `./config/{网站名}.json` 的 `rate_limit` 中配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    /// 每分钟最多的请求数，为0时不限制
+    pub requests_per_minute: u32,
+    /// 空闲后允许连续发出的请求数
+    pub burst: u32,
+    /// 同一主机两次请求之间的最小间隔（毫秒）
+    pub min_delay_ms: u64,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 60,
+            burst: 5,
+            min_delay_ms: 200,
+        }
+    }
+}
+
+/// 一个主机的令牌桶
+#[derive(Debug)]
+struct Bucket {
+    /// 可用的令牌，预约的请求会让它变为负数
+    tokens: f64,
+    /// 上次补充令牌的时间
+    refilled: Instant,
+    /// 最后一个预约的请求的发出时间
+    last_request: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst.max(1) as f64,
+            refilled: now,
+            last_request: None,
+        }
+    }
+
+    /// 预约一次请求，返回需要等待的时间
+    fn reserve(&mut self, limit: &RateLimit, now: Instant) -> Duration {
+        let per_second = limit.requests_per_minute as f64 / 60.0;
+        let burst = limit.burst.max(1) as f64;
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(burst);
+        self.refilled = now;
+
+        let mut wait = if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / per_second)
+        };
+        if let Some(last) = self.last_request {
+            let earliest = last + Duration::from_millis(limit.min_delay_ms);
+            wait = wait.max(earliest.saturating_duration_since(now));
+        }
+        self.tokens -= 1.0;
+        self.last_request = Some(now + wait);
+        wait
+    }
+}
+
+/// 按主机限制请求频率，所有下载后端共享
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    hosts: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 等待到可以向链接所在的主机发出请求
+    pub async fn acquire(&self, url: &str) {
+        if self.limit.requests_per_minute == 0 {
+            return;
+        }
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let wait = {
+            let now = Instant::now();
+            let mut hosts = self.hosts.lock().unwrap();
+            hosts
+                .entry(host.clone())
+                .or_insert_with(|| Bucket::new(&self.limit, now))
+                .reserve(&self.limit, now)
+        };
+        if !wait.is_zero() {
+            debug!("限速：{}等待{:?}", host, wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+static LIMITER: Lazy<RwLock<Arc<RateLimiter>>> =
+    Lazy::new(|| RwLock::new(Arc::new(RateLimiter::new(RateLimit::default()))));
+
+/// 设置全局的请求频率限制，配置改变时重新计数
+pub fn set_rate_limit(limit: RateLimit) {
+    let mut limiter = LIMITER.write().unwrap();
+    if limiter.limit != limit {
+        *limiter = Arc::new(RateLimiter::new(limit));
+    }
+}
+
+/// 全局的限速器
+pub fn rate_limiter() -> Arc<RateLimiter> {
+    LIMITER.read().unwrap().clone()
+}
+
+/// 每次请求前先经过全局限速器的后端
+pub struct LimitedBackend<B> {
+    inner: B,
+}
+
+impl<B> LimitedBackend<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner }
+    }
+}
+
+impl<B: FetchBackend> LimitedBackend<B> {
+    /// 按内层后端的设置重试，每次请求前都经过限速
+    async fn retry<T, F, Fut>(&self, url: &str, request: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let (retries, delay) = self.inner.retries();
+        let mut attempt = 0;
+        loop {
+            rate_limiter().acquire(url).await;
+            match request().await {
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    warn!("请求失败，第{}次重试：{} - {}", attempt, url, e);
+                    tokio::time::sleep(delay * attempt).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<B: FetchBackend> FetchBackend for LimitedBackend<B> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<Page> {
+        self.retry(url, || self.inner.fetch_page(url)).await
+    }
+
+    async fn fetch_pages(&self, url: &str) -> Result<Vec<Page>> {
+        self.retry(url, || self.inner.fetch_pages(url)).await
+    }
+
+    async fn fetch_bytes(&self, url: &str) -> Result<(Vec<u8>, Option<String>)> {
+        self.retry(url, || self.inner.fetch_bytes(url)).await
+    }
+
+    fn retries(&self) -> (u32, Duration) {
+        self.inner.retries()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_reserve() {
+        let limit = RateLimit {
+            requests_per_minute: 60,
+            burst: 2,
+            min_delay_ms: 100,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket::new(&limit, start);
+
+        // 突发的两个请求只受最小间隔限制
+        assert_eq!(bucket.reserve(&limit, start), Duration::ZERO);
+        assert_eq!(bucket.reserve(&limit, start), Duration::from_millis(100));
+        // 令牌用完后按每秒一个发放
+        assert_eq!(bucket.reserve(&limit, start), Duration::from_secs(1));
+        assert_eq!(bucket.reserve(&limit, start), Duration::from_secs(2));
+
+        // 空闲足够久后令牌补满，但不超过burst
+        let later = start + Duration::from_secs(60);
+        assert_eq!(bucket.reserve(&limit, later), Duration::ZERO);
+        assert_eq!(bucket.reserve(&limit, later), Duration::from_millis(100));
+        assert_eq!(bucket.reserve(&limit, later), Duration::from_secs(1));
+    }
+
+    /// 前两次失败，之后成功
+    struct Flaky(std::sync::atomic::AtomicU32);
+
+    #[async_trait]
+    impl FetchBackend for Flaky {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn fetch_page(&self, url: &str) -> Result<Page> {
+            match self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 | 1 => Err(anyhow::anyhow!("连接失败")),
+                _ => Ok(Page::new(url, 200, String::new())),
+            }
+        }
+
+        async fn fetch_bytes(&self, _url: &str) -> Result<(Vec<u8>, Option<String>)> {
+            Err(anyhow::anyhow!("连接失败"))
+        }
+
+        fn retries(&self) -> (u32, Duration) {
+            (3, Duration::ZERO)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_through_limiter() {
+        let backend = LimitedBackend::new(Flaky(Default::default()));
+        assert!(backend.fetch_page("https://retry.test/1").await.is_ok());
+        assert_eq!(backend.inner.0.load(std::sync::atomic::Ordering::SeqCst), 3);
+        // 重试用完后返回最后的错误，每次重试都经过了限速
+        assert!(backend.fetch_bytes("https://bytes.test/1").await.is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! 页面下载后端
+//!
+//! 来源通过 [`FetchBackend`] 获取页面和图片，后端可以是http客户端、single-file，
+//! 也可以是回放保存在磁盘上的页面，这样解析和整个下载流程都能离线测试。
+
+pub mod http;
+pub mod images;
+pub mod limit;
+pub mod record;
+pub mod replay;
+#[cfg(feature = "browser")]
+pub mod singlefile;
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::utils::fetch::limit::LimitedBackend;
+use crate::utils::fetch::record::{BlockCheck, RecordingBackend};
+
+/// 获取到的页面
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// 页面的链接
+    pub url: String,
+    pub status: u16,
+    pub body: String,
+}
+
+impl Page {
+    pub fn new(url: &str, status: u16, body: String) -> Self {
+        Self {
+            url: url.to_string(),
+            status,
+            body,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        200 <= self.status && self.status < 300
+    }
+
+    /// 状态码表示请求被限制或拒绝
+    pub fn is_throttled(&self) -> bool {
+        matches!(self.status, 403 | 429 | 503)
+    }
+
+    /// single-file输出的一项，链接取 `request.url`，没有时使用 `url`
+    pub fn from_singlefile(value: &Value, url: &str) -> Self {
+        let url = value
+            .pointer("/request/url")
+            .and_then(|v| v.as_str())
+            .unwrap_or(url);
+        let body = value["content"].as_str().unwrap_or_default().to_string();
+        Self::new(url, 200, body)
+    }
+}
+
+/// 被反爬页面拦截，下载流程遇到时会降低并发后重试
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blocked {
+    pub url: String,
+}
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "被反爬页面拦截：{}", self.url)
+    }
+}
+
+impl std::error::Error for Blocked {}
+
+/// 错误是否由反爬页面引起
+pub fn is_blocked(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<Blocked>())
+}
+
+/// 页面和图片的下载方式
+#[async_trait]
+pub trait FetchBackend: Send + Sync {
+    /// 后端名，用于日志
+    fn name(&self) -> &str;
+
+    /// 获取一个页面
+    async fn fetch_page(&self, url: &str) -> Result<Page>;
+
+    /// 获取从链接开始的所有页面，single-file爬取时会得到多个页面
+    async fn fetch_pages(&self, url: &str) -> Result<Vec<Page>> {
+        Ok(vec![self.fetch_page(url).await?])
+    }
+
+    /// 获取二进制内容和内容类型，用于下载图片
+    async fn fetch_bytes(&self, url: &str) -> Result<(Vec<u8>, Option<String>)>;
+
+    /// 请求失败时的重试次数和间隔，由限速的后端重试，每次重试都要重新限速
+    fn retries(&self) -> (u32, Duration) {
+        (0, Duration::ZERO)
+    }
+}
+
+/// 按运行模式包装下载后端：回放时替换为录制的内容，否则先限速，录制模式下再保存结果
+pub fn wrap<B: FetchBackend + 'static>(backend: B) -> Fetcher {
+    wrap_checked(backend, None)
+}
+
+/// 同 [`wrap`]，录制时跳过 `is_blocked` 判断为反爬页面的页面
+pub fn wrap_checked<B: FetchBackend + 'static>(
+    backend: B,
+    is_blocked: Option<BlockCheck>,
+) -> Fetcher {
+    if let Some(replay) = record::replay_fetcher() {
+        return replay;
+    }
+    match record::recorder() {
+        Some(recorder) => Fetcher::new(LimitedBackend::new(
+            RecordingBackend::new(backend, recorder).with_block_check(is_blocked),
+        )),
+        None => Fetcher::new(LimitedBackend::new(backend)),
+    }
+}
+
+/// 共享的下载后端
+#[derive(Clone)]
+pub struct Fetcher(Arc<dyn FetchBackend>);
+
+impl Fetcher {
+    pub fn new<B: FetchBackend + 'static>(backend: B) -> Self {
+        Self(Arc::new(backend))
+    }
+}
+
+impl Deref for Fetcher {
+    type Target = dyn FetchBackend;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
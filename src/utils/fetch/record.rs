@@ -0,0 +1,353 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use once_cell::sync::OnceCell;
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::utils::fetch::replay::{FixtureIndex, INDEX_FILE, ReplayBackend};
+use crate::utils::fetch::{FetchBackend, Fetcher, Page};
+use crate::utils::html::image_filename;
+use crate::utils::snapshot;
+
+/// 下载后端的运行模式
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchMode {
+    /// 正常访问网络
+    Live,
+    /// 访问网络，同时把页面和图片保存到目录
+    Record(PathBuf),
+    /// 只从录制的目录读取，不访问网络
+    Replay(PathBuf),
+}
+
+static MODE: OnceCell<FetchMode> = OnceCell::new();
+static RECORDER: OnceCell<Arc<Recorder>> = OnceCell::new();
+static REPLAY: OnceCell<Fetcher> = OnceCell::new();
+
+/// 设置运行模式，只能在创建下载后端之前设置一次
+pub fn set_fetch_mode(mode: FetchMode) -> Result<()> {
+    match &mode {
+        FetchMode::Record(dir) => {
+            let recorder = Recorder::create(dir)?;
+            RECORDER
+                .set(Arc::new(recorder))
+                .map_err(|_| anyhow!("录制模式已经设置"))?;
+            info!("录制模式：{:?}", dir);
+        }
+        FetchMode::Replay(dir) => {
+            let backend = ReplayBackend::open(dir)?;
+            REPLAY
+                .set(Fetcher::new(backend))
+                .map_err(|_| anyhow!("回放模式已经设置"))?;
+            info!("回放模式：{:?}", dir);
+        }
+        FetchMode::Live => {}
+    }
+    MODE.set(mode).map_err(|_| anyhow!("下载模式已经设置"))
+}
+
+/// 当前的运行模式，没有设置时为 [`FetchMode::Live`]
+pub fn fetch_mode() -> &'static FetchMode {
+    MODE.get().unwrap_or(&FetchMode::Live)
+}
+
+/// 是否为回放模式，回放时不启动浏览器
+pub fn is_replay() -> bool {
+    matches!(fetch_mode(), FetchMode::Replay(_))
+}
+
+/// 录制模式下的录制目录
+pub fn recorder() -> Option<Arc<Recorder>> {
+    RECORDER.get().cloned()
+}
+
+/// 回放模式下的回放后端
+pub fn replay_fetcher() -> Option<Fetcher> {
+    REPLAY.get().cloned()
+}
+
+/// 保存浏览器扩展上传的图片，不在录制模式时什么也不做
+pub fn record_image(url: &str, data: &[u8]) {
+    if let Some(recorder) = RECORDER.get() {
+        recorder.save_image(url, data);
+    }
+}
+
+/// 判断页面是否为反爬页面，录制时跳过这些页面
+pub type BlockCheck = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// 录制目录，格式与 [`ReplayBackend`] 读取的相同
+pub struct Recorder {
+    dir: PathBuf,
+    index: Mutex<RecordIndex>,
+}
+
+/// 索引和下一个文件的序号，序号只增不减，重新录制同一个链接时覆盖原来的文件
+struct RecordIndex {
+    index: FixtureIndex,
+    next_page: usize,
+    next_image: usize,
+}
+
+impl RecordIndex {
+    fn new(index: FixtureIndex) -> Self {
+        Self {
+            next_page: next_number(index.pages.values()),
+            next_image: next_number(index.images.values()),
+            index,
+        }
+    }
+
+    /// 链接已经录制过时使用原来的文件名，否则使用新的序号
+    fn page_file(&mut self, url: &str, ext: &str) -> String {
+        match self.index.pages.get(url) {
+            Some(file) if file.ends_with(ext) => file.clone(),
+            _ => {
+                self.next_page += 1;
+                format!("pages/{:04}{}", self.next_page - 1, ext)
+            }
+        }
+    }
+
+    fn image_file(&mut self, url: &str) -> String {
+        match self.index.images.get(url) {
+            Some(file) => file.clone(),
+            None => {
+                self.next_image += 1;
+                format!("images/{:04}-{}", self.next_image - 1, image_filename(url))
+            }
+        }
+    }
+}
+
+/// 已有文件中最大的序号加一，文件名形如 `pages/0001.html`、`images/0001-1.jpg`
+fn next_number<'a>(files: impl Iterator<Item = &'a String>) -> usize {
+    files
+        .filter_map(|f| {
+            let name = f.rsplit('/').next()?;
+            name.get(..4)?.parse::<usize>().ok()
+        })
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+impl Recorder {
+    /// 创建录制目录，已有的录制会被保留并追加
+    pub fn create<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        for sub in ["pages", "images"] {
+            std::fs::create_dir_all(dir.join(sub))
+                .with_context(|| format!("创建录制目录失败：{:?}", dir))?;
+        }
+        let index = snapshot::load_with_backup(dir.join(INDEX_FILE), |path| {
+            Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+        })
+        .unwrap_or_default();
+        Ok(Self {
+            dir,
+            index: Mutex::new(RecordIndex::new(index)),
+        })
+    }
+
+    /// 保存页面，多个页面（single-file爬取的结果）保存为json
+    pub fn save_pages(&self, url: &str, pages: &[Page]) {
+        let mut index = self.index.lock().unwrap();
+        let (file, content) = match pages {
+            [page] => (index.page_file(url, ".html"), page.body.clone()),
+            _ => {
+                let values: Vec<_> = pages
+                    .iter()
+                    .map(|p| json!({"content": p.body, "request": {"url": p.url}}))
+                    .collect();
+                let content = serde_json::to_string_pretty(&values).unwrap_or_default();
+                (index.page_file(url, ".json"), content)
+            }
+        };
+        if let Err(e) = std::fs::write(self.dir.join(&file), content) {
+            error!("保存录制的页面失败 url:{}\terror:{}", url, e);
+            return;
+        }
+        index.index.pages.insert(url.to_string(), file);
+        self.save_index(&index.index);
+    }
+
+    /// 保存图片
+    pub fn save_image(&self, url: &str, data: &[u8]) {
+        let mut index = self.index.lock().unwrap();
+        let file = index.image_file(url);
+        if let Err(e) = std::fs::write(self.dir.join(&file), data) {
+            error!("保存录制的图片失败 url:{}\terror:{}", url, e);
+            return;
+        }
+        index.index.images.insert(url.to_string(), file);
+        self.save_index(&index.index);
+    }
+
+    /// 先写入临时文件再替换，录制中断时索引不会损坏
+    fn save_index(&self, index: &FixtureIndex) {
+        let result = snapshot::write_atomic(self.dir.join(INDEX_FILE), |writer| {
+            Ok(serde_json::to_writer_pretty(writer, index)?)
+        });
+        if let Err(e) = result {
+            error!("保存录制索引失败：{}", e);
+        }
+    }
+}
+
+/// 录制下载结果的后端，只保存成功并且不是反爬页面的响应
+pub struct RecordingBackend<B> {
+    inner: B,
+    recorder: Arc<Recorder>,
+    is_blocked: Option<BlockCheck>,
+}
+
+impl<B> RecordingBackend<B> {
+    pub fn new(inner: B, recorder: Arc<Recorder>) -> Self {
+        Self {
+            inner,
+            recorder,
+            is_blocked: None,
+        }
+    }
+
+    /// 设置反爬页面的判断，为空时只跳过状态码异常的页面
+    pub fn with_block_check(mut self, is_blocked: Option<BlockCheck>) -> Self {
+        self.is_blocked = is_blocked;
+        self
+    }
+
+    fn should_record(&self, page: &Page) -> bool {
+        page.is_success() && !self.is_blocked.as_ref().is_some_and(|f| f(&page.body))
+    }
+}
+
+#[async_trait]
+impl<B: FetchBackend> FetchBackend for RecordingBackend<B> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<Page> {
+        let page = self.inner.fetch_page(url).await?;
+        if self.should_record(&page) {
+            self.recorder.save_pages(url, std::slice::from_ref(&page));
+        }
+        Ok(page)
+    }
+
+    async fn fetch_pages(&self, url: &str) -> Result<Vec<Page>> {
+        let pages = self.inner.fetch_pages(url).await?;
+        if !pages.is_empty() && pages.iter().all(|p| self.should_record(p)) {
+            self.recorder.save_pages(url, &pages);
+        }
+        Ok(pages)
+    }
+
+    async fn fetch_bytes(&self, url: &str) -> Result<(Vec<u8>, Option<String>)> {
+        let (data, content_type) = self.inner.fetch_bytes(url).await?;
+        self.recorder.save_image(url, &data);
+        Ok((data, content_type))
+    }
+
+    fn retries(&self) -> (u32, Duration) {
+        self.inner.retries()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake;
+
+    #[async_trait]
+    impl FetchBackend for Fake {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        async fn fetch_page(&self, url: &str) -> Result<Page> {
+            if url.ends_with("blocked") {
+                return Ok(Page::new(url, 200, "Just a moment...".to_string()));
+            }
+            Ok(Page::new(url, 200, format!("<p>{}</p>", url)))
+        }
+
+        async fn fetch_pages(&self, url: &str) -> Result<Vec<Page>> {
+            Ok(vec![
+                Page::new(url, 200, "<p>1</p>".to_string()),
+                Page::new(&format!("{}_2", url), 200, "<p>2</p>".to_string()),
+            ])
+        }
+
+        async fn fetch_bytes(&self, _url: &str) -> Result<(Vec<u8>, Option<String>)> {
+            Ok((vec![1, 2, 3], None))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let is_blocked: BlockCheck = Arc::new(|html| html.contains("Just a moment"));
+        let recording =
+            RecordingBackend::new(Fake, Arc::new(Recorder::create(dir.path()).unwrap()))
+                .with_block_check(Some(is_blocked));
+        // 重复录制同一个链接不会占用新的序号
+        recording.fetch_page("https://example.com/a").await.unwrap();
+        recording.fetch_page("https://example.com/a").await.unwrap();
+        recording
+            .fetch_page("https://example.com/blocked")
+            .await
+            .unwrap();
+        recording
+            .fetch_pages("https://example.com/b")
+            .await
+            .unwrap();
+        recording
+            .fetch_bytes("https://example.com/1.jpg")
+            .await
+            .unwrap();
+
+        let replay = ReplayBackend::open(dir.path()).unwrap();
+        let page = replay.fetch_page("https://example.com/a").await.unwrap();
+        assert_eq!(page.body, "<p>https://example.com/a</p>");
+        let pages = replay.fetch_pages("https://example.com/b").await.unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].url, "https://example.com/b_2");
+        let (data, mime) = replay
+            .fetch_bytes("https://example.com/1.jpg")
+            .await
+            .unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+        assert_eq!(mime.as_deref(), Some("image/jpeg"));
+        assert!(
+            replay
+                .fetch_page("https://example.com/blocked")
+                .await
+                .is_err()
+        );
+
+        let index: FixtureIndex =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join(INDEX_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(index.pages["https://example.com/a"], "pages/0001.html");
+        assert_eq!(index.pages["https://example.com/b"], "pages/0002.json");
+
+        // 追加录制时从已有的最大序号继续
+        let recorder = Recorder::create(dir.path()).unwrap();
+        recorder.save_pages(
+            "https://example.com/c",
+            &[Page::new("https://example.com/c", 200, String::new())],
+        );
+        let index = recorder.index.lock().unwrap();
+        assert_eq!(
+            index.index.pages["https://example.com/c"],
+            "pages/0003.html"
+        );
+    }
+}
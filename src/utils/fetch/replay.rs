@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::utils::fetch::{FetchBackend, Page};
+use crate::utils::html::image_mime_type;
+
+/// 录制目录的索引文件名
+pub const INDEX_FILE: &str = "index.json";
+
+/// 录制目录的索引，链接到文件名（相对于录制目录）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FixtureIndex {
+    /// 页面，`.html` 为原始页面，`.json` 为single-file的输出
+    pub pages: HashMap<String, String>,
+    /// 图片
+    pub images: HashMap<String, String>,
+}
+
+/// 回放保存在磁盘上的页面和图片，不访问网络
+pub struct ReplayBackend {
+    dir: PathBuf,
+    index: FixtureIndex,
+}
+
+impl ReplayBackend {
+    /// 打开录制目录，读取其中的 `index.json`
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let path = dir.join(INDEX_FILE);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("读取录制索引失败：{:?}", path))?;
+        let index = serde_json::from_str(&content)
+            .with_context(|| format!("解析录制索引失败：{:?}", path))?;
+        Ok(Self { dir, index })
+    }
+
+    fn file_for<'a>(&self, files: &'a HashMap<String, String>, url: &str) -> Result<&'a String> {
+        files
+            .get(url)
+            .ok_or_else(|| anyhow!("没有录制的链接：{}", url))
+    }
+}
+
+#[async_trait]
+impl FetchBackend for ReplayBackend {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<Page> {
+        self.fetch_pages(url)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("录制的页面为空：{}", url))
+    }
+
+    async fn fetch_pages(&self, url: &str) -> Result<Vec<Page>> {
+        let file = self.file_for(&self.index.pages, url)?;
+        let path = self.dir.join(file);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("读取录制的页面失败：{:?}", path))?;
+        if file.ends_with(".json") {
+            let values: Vec<Value> = serde_json::from_str(&content)
+                .with_context(|| format!("解析录制的页面失败：{:?}", path))?;
+            Ok(values
+                .iter()
+                .map(|v| Page::from_singlefile(v, url))
+                .collect())
+        } else {
+            Ok(vec![Page::new(url, 200, content)])
+        }
+    }
+
+    async fn fetch_bytes(&self, url: &str) -> Result<(Vec<u8>, Option<String>)> {
+        let file = self.file_for(&self.index.images, url)?;
+        let path = self.dir.join(file);
+        let data =
+            std::fs::read(&path).with_context(|| format!("读取录制的图片失败：{:?}", path))?;
+        Ok((data, Some(image_mime_type(file).to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(INDEX_FILE),
+            r#"{
+                "pages": {"https://example.com/a": "a.html", "https://example.com/b": "b.json"},
+                "images": {"https://example.com/1.png": "1.png"}
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("a.html"), "<p>a</p>").unwrap();
+        std::fs::write(
+            dir.path().join("b.json"),
+            r#"[{"content": "<p>b</p>", "request": {"url": "https://example.com/b"}},
+                {"content": "<p>b2</p>", "request": {"url": "https://example.com/b_2"}}]"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("1.png"), [137u8, 80, 78, 71]).unwrap();
+
+        let backend = ReplayBackend::open(dir.path()).unwrap();
+        let page = backend.fetch_page("https://example.com/a").await.unwrap();
+        assert_eq!(page.body, "<p>a</p>");
+        assert!(page.is_success());
+
+        let pages = backend.fetch_pages("https://example.com/b").await.unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].url, "https://example.com/b_2");
+
+        let (data, mime) = backend
+            .fetch_bytes("https://example.com/1.png")
+            .await
+            .unwrap();
+        assert_eq!(data, vec![137u8, 80, 78, 71]);
+        assert_eq!(mime.as_deref(), Some("image/png"));
+
+        let err = backend.fetch_page("https://example.com/c").await;
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::Value;
+use tracing::error;
+
+use crate::utils::config::DynamicConfig;
+use crate::utils::download::downl::down::download_from_url;
+use crate::utils::fetch::{FetchBackend, Page};
+
+/// 通过single-file命令行工具下载页面，需要已经启动的浏览器
+pub struct SingleFileBackend {
+    config: DynamicConfig,
+}
+
+impl SingleFileBackend {
+    /// `config` 为single-file的配置，包括浏览器地址、爬取规则等
+    pub fn new(config: DynamicConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl FetchBackend for SingleFileBackend {
+    fn name(&self) -> &str {
+        "single-file"
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<Page> {
+        self.fetch_pages(url)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                error!("没有从{}得到任何内容", url);
+                anyhow!("没有从{}得到任何内容", url)
+            })
+    }
+
+    async fn fetch_pages(&self, url: &str) -> Result<Vec<Page>> {
+        let values: Vec<Value> = download_from_url(url, self.config.clone()).await?;
+        Ok(values
+            .iter()
+            .map(|v| Page::from_singlefile(v, url))
+            .collect())
+    }
+
+    async fn fetch_bytes(&self, url: &str) -> Result<(Vec<u8>, Option<String>)> {
+        Err(anyhow!("single-file不能直接下载图片：{}", url))
+    }
+}
@@ -0,0 +1,197 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use scraper::{ElementRef, Html, Selector};
+use tracing::error;
+use url::Url;
+use visdom::Vis;
+
+use crate::utils::epub::default_css::BROKEN_IMAGE_BASE64;
+
+///提取章节内容，并处理img标签
+pub fn extract_chapter(
+    html: &str,
+    selector: &str,
+    src_name: &str,
+    remove_vec: Vec<&str>,
+) -> Result<(Vec<String>, String)> {
+    let html =
+        Vis::load(Html::parse_document(html).html()).map_err(|e| anyhow!("html解析失败 {}", e))?;
+    let text = html.find(selector).first();
+    let items = text.children("");
+    // items.filter("div").remove();
+    items.filter("p.sf-hidden").remove();
+
+    for se in remove_vec {
+        let items = text.children("");
+        items.filter(se).remove();
+    }
+    // items.filter_by(handle)
+    let mut src_vec = Vec::new();
+    text.find("img").for_each(|_index, ele| {
+        let mut img_ele = Vis::dom(ele);
+        // 2. 检查是否存在目标属性（src_name，比如 "data-src" 或 "src"）
+        if img_ele.has_attr(src_name) {
+            if let Some(attr_value) = img_ele.attr(src_name) {
+                let url = match Url::parse(&attr_value.to_string()) {
+                    Ok(u) => u.to_string(),
+                    Err(_) => String::new(),
+                };
+                let filename = Path::new(&url)
+                    .file_name() // 取最后一个组件（如"a/b/c.jpg"→"c.jpg"）
+                    .and_then(|os_str| os_str.to_str()) // 转&str（处理非UTF8）
+                    .unwrap_or(""); // 任何错误都返回空字符串
+
+                let src_val = attr_value.to_string();
+                if !filename.is_empty() {
+                    img_ele.set_attr("src", Some(&format!("images/{}", filename)));
+                    src_vec.push(src_val);
+                } else {
+                    img_ele.set_attr("src", Some(BROKEN_IMAGE_BASE64));
+                    src_vec.push(src_val);
+                }
+            }
+        } else {
+            img_ele.set_attr("src", Some(BROKEN_IMAGE_BASE64));
+        }
+        true
+    });
+    Ok((src_vec, text.html().trim().to_string()))
+}
+
+///解析css选择器
+pub fn parse_selector(selector: &str) -> Result<Selector> {
+    Selector::parse(selector).map_err(|e| {
+        error!("{}", e);
+        anyhow!("css选择器无效 {}：{}", selector, e)
+    })
+}
+
+///元素的文本，去掉首尾空白
+fn element_text(element: &ElementRef) -> String {
+    element.text().collect::<String>().trim().to_string()
+}
+
+///第一个匹配元素的文本，没有匹配时为空
+pub fn select_text(html: &Html, selector: &str) -> Result<String> {
+    let selector = parse_selector(selector)?;
+    Ok(html
+        .select(&selector)
+        .next()
+        .map(|e| element_text(&e))
+        .unwrap_or_default())
+}
+
+///所有匹配元素的文本，忽略空文本
+pub fn select_all_text(html: &Html, selector: &str) -> Result<Vec<String>> {
+    let selector = parse_selector(selector)?;
+    Ok(html
+        .select(&selector)
+        .map(|e| element_text(&e))
+        .filter(|s| !s.is_empty())
+        .collect())
+}
+
+///第一个匹配元素的属性，没有匹配时为空
+pub fn select_attr(html: &Html, selector: &str, attr: &str) -> Result<String> {
+    let selector = parse_selector(selector)?;
+    Ok(html
+        .select(&selector)
+        .next()
+        .and_then(|e| e.value().attr(attr))
+        .unwrap_or_default()
+        .to_string())
+}
+
+///容器中的所有链接，返回（绝对链接，名称），名称取自 `name_attr` 属性，没有时为文本
+pub fn select_links(
+    html: &Html,
+    container: &str,
+    link: &str,
+    name_attr: Option<&str>,
+    base_url: &str,
+) -> Result<Vec<(String, String)>> {
+    let container = parse_selector(container)?;
+    let link = parse_selector(link)?;
+    let base = Url::parse(base_url).ok();
+    let Some(element) = html.select(&container).next() else {
+        return Ok(Vec::new());
+    };
+    Ok(element
+        .select(&link)
+        .map(|e| {
+            let href = e.value().attr("href").unwrap_or_default();
+            let url = match &base {
+                Some(base) => base.join(href).map(|u| u.to_string()).unwrap_or_default(),
+                None => href.to_string(),
+            };
+            let name = name_attr
+                .and_then(|attr| e.value().attr(attr))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_else(|| element_text(&e));
+            (url, name)
+        })
+        .collect())
+}
+
+///图片链接中的文件名，打包时图片保存在 `images/{文件名}`
+pub fn image_filename(url: &str) -> String {
+    Path::new(url)
+        .file_name()
+        .and_then(|os_str| os_str.to_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+///根据文件扩展名推断图片类型
+pub fn image_mime_type(filename: &str) -> &'static str {
+    let ext = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        _ => "image/jpeg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_links() {
+        let html = Html::parse_document(
+            r#"<div class="list"><a href="/b/1.html" title="第一卷"> 1 </a><a href="https://example.com/b/2.html">第二卷</a></div>"#,
+        );
+        let links = select_links(
+            &html,
+            "div.list",
+            "a",
+            Some("title"),
+            "https://example.com/b/",
+        )
+        .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                (
+                    "https://example.com/b/1.html".to_string(),
+                    "第一卷".to_string()
+                ),
+                (
+                    "https://example.com/b/2.html".to_string(),
+                    "第二卷".to_string()
+                ),
+            ]
+        );
+        assert_eq!(select_text(&html, "a").unwrap(), "1");
+        assert_eq!(select_all_text(&html, "a").unwrap(), vec!["1", "第二卷"]);
+        assert_eq!(select_attr(&html, "a", "href").unwrap(), "/b/1.html");
+    }
+}
@@ -92,7 +92,7 @@ impl HttpClient {
                     last_error = Some(e);
                     if attempt < self.config.max_retries {
                         println!("请求失败，第 {} 次重试...", attempt + 1);
-                        std::thread::sleep(self.config.retry_delay * (attempt + 1) as u32);
+                        std::thread::sleep(self.config.retry_delay * (attempt + 1));
                     }
                 }
             }
@@ -136,7 +136,7 @@ impl HttpClient {
                     last_error = Some(e);
                     if attempt < self.config.max_retries {
                         println!("请求失败，第 {} 次重试...", attempt + 1);
-                        std::thread::sleep(self.config.retry_delay * (attempt + 1) as u32);
+                        std::thread::sleep(self.config.retry_delay * (attempt + 1));
                     }
                 }
             }
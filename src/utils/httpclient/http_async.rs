@@ -6,12 +6,14 @@ use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
 use tokio::time::sleep;
 use anyhow::{Result, Context};
+use tracing::warn;
 
 use crate::utils::httpclient::types::*;
 
 pub struct AsyncHttpClient {
     client: reqwest::Client,
     user_agent: String,
+    headers: Vec<(String, String)>,
     cache: Option<Cache<u64, HttpResponse>>,
     config: RequestConfig,
 }
@@ -41,16 +43,36 @@ impl AsyncHttpClient {
         Ok(Self {
             client,
             user_agent: "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36".to_string(),
+            headers: Vec::new(),
             cache,
             config,
         })
     }
     
+    // 请求的重试和超时设置
+    pub fn config(&self) -> &RequestConfig {
+        &self.config
+    }
+
     // 设置自定义 User-Agent
     pub fn set_user_agent(&mut self, user_agent: String) {
         self.user_agent = user_agent;
     }
     
+    // 设置每个请求都附加的请求头
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    // 创建 GET 请求，附加 User-Agent 和自定义请求头
+    fn build_get(&self, url: &str) -> reqwest::RequestBuilder {
+        self.headers.iter().fold(
+            self.client.get(url).header("User-Agent", &self.user_agent),
+            |request, (name, value)| request.header(name, value),
+        )
+    }
+
     // 生成请求的哈希键（用于缓存）
     fn generate_cache_key(&self, method: &str, url: &str, body: Option<&str>) -> u64 {
         let mut hasher = DefaultHasher::new();
@@ -99,8 +121,8 @@ impl AsyncHttpClient {
                 Err(e) => {
                     last_error = Some(e);
                     if attempt < self.config.max_retries {
-                        println!("请求失败，第 {} 次重试...", attempt + 1);
-                        sleep(self.config.retry_delay * (attempt + 1) as u32).await;
+                        warn!("请求失败，第 {} 次重试：{}", attempt + 1, url);
+                        sleep(self.config.retry_delay * (attempt + 1)).await;
                     }
                 }
             }
@@ -109,11 +131,37 @@ impl AsyncHttpClient {
         Err(last_error.unwrap())
     }
     
+    // 发送一次 GET 请求（带缓存，不重试），由调用方重试
+    pub async fn get_once(&self, url: &str) -> Result<HttpResponse> {
+        let cache_key = self.generate_cache_key("GET", url, None);
+        if let Some(cached_response) = self.check_cache(cache_key).await {
+            return Ok(cached_response);
+        }
+        let response = self.execute_get(url).await?;
+        self.store_in_cache(cache_key, response.clone()).await;
+        Ok(response)
+    }
+
+    // 下载一次二进制内容（不重试，不缓存），返回数据和内容类型
+    pub async fn get_bytes(&self, url: &str) -> Result<(Vec<u8>, Option<String>)> {
+        let response = self.build_get(url)
+            .send()
+            .await
+            .context("发送 GET 请求失败")?
+            .error_for_status()
+            .context("请求返回错误状态")?;
+
+        let content_type = response.headers()
+            .get("content-type")
+            .and_then(|ct| ct.to_str().ok())
+            .map(|s| s.to_string());
+        let bytes = response.bytes().await.context("读取响应体失败")?;
+        Ok((bytes.to_vec(), content_type))
+    }
+
     // 执行 GET 请求（不包含重试逻辑）
     async fn execute_get(&self, url: &str) -> Result<HttpResponse> {
-        let response = self.client
-            .get(url)
-            .header("User-Agent", &self.user_agent)
+        let response = self.build_get(url)
             .send()
             .await
             .context("发送 GET 请求失败")?;
@@ -145,8 +193,8 @@ impl AsyncHttpClient {
                 Err(e) => {
                     last_error = Some(e);
                     if attempt < self.config.max_retries {
-                        println!("请求失败，第 {} 次重试...", attempt + 1);
-                        sleep(self.config.retry_delay * (attempt + 1) as u32).await;
+                        warn!("请求失败，第 {} 次重试：{}", attempt + 1, url);
+                        sleep(self.config.retry_delay * (attempt + 1)).await;
                     }
                 }
             }
@@ -12,6 +12,20 @@ pub struct ImageData {
     pub file_path: Option<String>, 
 }
 
+impl ImageData {
+    /// 从下载的数据创建，不保存到文件
+    pub fn new(filename: &str, mime_type: &str, data: Vec<u8>) -> Self {
+        use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+        Self {
+            base64_data: BASE64.encode(&data),
+            u8_data: data,
+            filename: filename.to_string(),
+            mime_type: mime_type.to_string(),
+            file_path: None,
+        }
+    }
+}
+
 /// 应用程序配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
@@ -1,20 +1,27 @@
+#[cfg(feature = "server")]
 use anyhow::anyhow;
 use flate2::{Compression, write::GzEncoder, read::GzDecoder};
 use memmap2::MmapOptions;
 use std::collections::HashMap;
-use std::io::BufWriter;
+use std::path::Path;
+use std::io::{BufWriter, Write};
 use std::sync::{Arc, RwLock};
 use std::thread;
+#[cfg(feature = "server")]
 use std::time::Duration;
 
 use super::config::{AppConfig, ImageData};
+use crate::core::state::StateHeader;
+use crate::utils::snapshot;
+#[cfg(feature = "server")]
 use super::server::HttpServer;
-use crate::Result;
+use anyhow::Result;
 
 /// 主控制器，用于管理服务器和配置
 pub struct Controller {
     config: Arc<RwLock<AppConfig>>,
     server_handle: RwLock<Option<thread::JoinHandle<()>>>,
+    #[cfg(feature = "server")]
     server: RwLock<Option<Arc<HttpServer>>>,
     should_stop: Arc<std::sync::atomic::AtomicBool>,
     // 使用HashMap存储图片数据，URL作为键
@@ -27,6 +34,7 @@ impl Controller {
         Self {
             config: Arc::new(RwLock::new(config)),
             server_handle: RwLock::new(None),
+            #[cfg(feature = "server")]
             server: RwLock::new(None),
             should_stop: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             images: Arc::new(RwLock::new(HashMap::new())),
@@ -34,6 +42,7 @@ impl Controller {
     }
 
     /// 启动 HTTP 服务器
+    #[cfg(feature = "server")]
     pub fn start_server(&self) -> Result<()> {
         let mut server_handle = self.server_handle.write().unwrap();
         if server_handle.is_some() {
@@ -66,6 +75,7 @@ impl Controller {
     }
 
     /// 停止 HTTP 服务器
+    #[cfg(feature = "server")]
     pub fn stop_server(&self) -> Result<()> {
         // 设置停止标志
         self.should_stop
@@ -149,23 +159,9 @@ impl Controller {
             images_lock.insert(url, image_data);
         }
     }
-    /// 从文件加载图片数据
+    /// 从文件加载图片数据，文件损坏时使用上一次保存的文件
     pub fn load_images_from_file(&self, filename: &str) -> Result<()> {
-        use std::io::Read;
-
-        // 打开文件并创建内存映射
-        let file = std::fs::File::open(filename)?;
-        let mmap = unsafe { MmapOptions::new().map(&file)? };
-
-        // 解压缩
-        let mut decoder = GzDecoder::new(&mmap[..]);
-        let mut buffer = Vec::new();
-        decoder.read_to_end(&mut buffer)?;
-
-        // 使用 Bincode 2 的正确方式 - 从切片解码
-        // decode_from_slice 返回 (T, usize)，我们只需要第一个元素
-        let (decoded, _): (HashMap<String, ImageData>, _) =
-            bincode::serde::decode_from_slice(&buffer, bincode::config::standard())?;
+        let decoded = snapshot::load_with_backup(filename, read_images)?;
 
         // 设置图片数据
         *self.images.write().unwrap() = decoded;
@@ -173,30 +169,41 @@ impl Controller {
         Ok(())
     }
 
-    /// 保存图片数据到文件
-    pub fn save_images_to_file(&self, filename: &str, compression_level: u32) -> Result<()> {
+    /// 保存图片数据到文件，先写入临时文件再替换，原来的文件保留为 `.bak`
+    pub fn save_images_to_file(
+        &self,
+        filename: &str,
+        header: &StateHeader,
+        compression_level: u32,
+    ) -> Result<()> {
 
         // 获取图片数据
         let images = self.images.read().unwrap();
-
-        // 创建文件并设置缓冲区
-        let file = std::fs::File::create(filename)?;
-        let writer = BufWriter::new(file);
-
-        // 使用压缩
-        let mut encoder = GzEncoder::new(writer, Compression::new(compression_level));
-
-        // 使用 Bincode 2 的正确方式 - 直接编码到写入器
-        bincode::serde::encode_into_std_write(&*images, &mut encoder, bincode::config::standard())?;
-
-        encoder.finish()?;
-
-        Ok(())
+        let header = serde_json::to_vec(&StateHeader {
+            format: IMAGES_FORMAT,
+            ..header.clone()
+        })?;
+
+        snapshot::write_atomic(filename, |writer| {
+            // 文件头：标识、格式版本和与状态文件相同的文件头
+            writer.write_all(IMAGES_MAGIC)?;
+            writer.write_all(&IMAGES_FORMAT.to_le_bytes())?;
+            writer.write_all(&(header.len() as u32).to_le_bytes())?;
+            writer.write_all(&header)?;
+
+            // 使用压缩
+            let mut encoder = GzEncoder::new(writer, Compression::new(compression_level));
+
+            // 使用 Bincode 2 的正确方式 - 直接编码到写入器
+            bincode::serde::encode_into_std_write(&*images, &mut encoder, bincode::config::standard())?;
+
+            encoder.finish()?;
+            Ok(())
+        })
     }
 
     /// 将图片数据保存为JSON格式的文本文件
     pub fn save_images_to_json_file(&self, filename: &str, pretty: bool) -> Result<()> {
-        use std::io::Write;
         
         // 获取图片数据
         let images = self.images.read().unwrap();
@@ -231,3 +238,96 @@ impl Controller {
             .collect()
     }
 }
+
+/// 图片文件的标识，后面是小端的格式版本，没有标识的旧文件为版本1
+const IMAGES_MAGIC: &[u8; 8] = b"NPIMAGES";
+/// 图片文件的格式版本，版本2在格式版本后是小端的长度和json格式的 [`StateHeader`]
+const IMAGES_FORMAT: u32 = 2;
+
+/// 读取图片文件的文件头，版本1的文件没有，返回 None
+pub fn read_images_header(path: &Path) -> Result<Option<StateHeader>> {
+    let file = std::fs::File::open(path)?;
+    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    Ok(split_images_file(&mmap, path)?.1)
+}
+
+/// 将图片文件分为格式版本、文件头和压缩的数据
+fn split_images_file<'a>(
+    content: &'a [u8],
+    path: &Path,
+) -> Result<(u32, Option<StateHeader>, &'a [u8])> {
+    let incomplete = || anyhow::anyhow!("图片文件不完整：{}", path.display());
+    // 版本1没有文件头，直接是压缩的数据
+    let Some(rest) = content.strip_prefix(IMAGES_MAGIC.as_slice()) else {
+        return Ok((1, None, content));
+    };
+    let (format, rest) = rest.split_first_chunk::<4>().ok_or_else(incomplete)?;
+    let format = u32::from_le_bytes(*format);
+    let (len, rest) = rest.split_first_chunk::<4>().ok_or_else(incomplete)?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if rest.len() < len {
+        return Err(incomplete());
+    }
+    let (header, data) = rest.split_at(len);
+    Ok((format, Some(serde_json::from_slice(header)?), data))
+}
+
+/// 读取压缩后的图片数据，旧版本的文件会被升级
+fn read_images(path: &Path) -> Result<HashMap<String, ImageData>> {
+    use std::io::Read;
+
+    // 打开文件并创建内存映射
+    let file = std::fs::File::open(path)?;
+    let mmap = unsafe { MmapOptions::new().map(&file)? };
+
+    let (format, _, data) = split_images_file(&mmap, path)?;
+    if format > IMAGES_FORMAT {
+        return Err(anyhow::anyhow!(
+            "图片文件的格式版本为{}，当前程序只支持到版本{}，请使用新版本的程序恢复下载，或者删除下载数据后重新下载：{}",
+            format,
+            IMAGES_FORMAT,
+            path.display()
+        ));
+    }
+
+    // 解压缩
+    let mut decoder = GzDecoder::new(data);
+    let mut buffer = Vec::new();
+    decoder.read_to_end(&mut buffer)?;
+
+    // 使用 Bincode 2 的正确方式 - 从切片解码
+    // decode_from_slice 返回 (T, usize)，我们只需要第一个元素
+    // 各个版本的数据相同，以后修改 ImageData 时在这里升级
+    let (decoded, _): (HashMap<String, ImageData>, _) =
+        bincode::serde::decode_from_slice(&buffer, bincode::config::standard())?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_images_file_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("images");
+        let filename = path.to_str().unwrap();
+        let controller = Controller::new(AppConfig::default());
+        controller.add_or_update_images(HashMap::from([(
+            "https://img/1.jpg".to_string(),
+            ImageData::new("1.jpg", "image/jpeg", vec![1, 2, 3]),
+        )]));
+        let header = StateHeader::new("bilinovel", "2356");
+        controller.save_images_to_file(filename, &header, 6).unwrap();
+
+        let saved = read_images_header(&path).unwrap().unwrap();
+        assert_eq!(saved.format, IMAGES_FORMAT);
+        assert_eq!(saved.book_id, "2356");
+        assert_eq!(saved.created, header.created);
+
+        let loaded = Controller::new(AppConfig::default());
+        loaded.load_images_from_file(filename).unwrap();
+        let image = loaded.get_image_by_url("https://img/1.jpg").unwrap();
+        assert_eq!(image.u8_data, vec![1, 2, 3]);
+    }
+}
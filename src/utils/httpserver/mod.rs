@@ -1,18 +1,23 @@
 //! Rust Controller Library
 //!
 //! 一个简单灵活的 HTTP 服务器控制库，支持配置管理和文件上传功能。
+//!
+//! 图片存储总是可用，接收浏览器扩展上传图片的 HTTP 服务器需要启用 `server` 功能。
 
 mod config;
 mod controller;
+#[cfg(feature = "server")]
 mod multipart;
+#[cfg(feature = "server")]
 mod server; // 修改：将upload改为multipart
 
 pub use config::{AppConfig, ImageData};
-pub use controller::Controller;
+pub use controller::{Controller, read_images_header};
 
 // 使用 anyhow 作为错误处理库
 pub use anyhow::Result;
 
+use crate::core::state::StateHeader;
 use once_cell::sync::OnceCell;
 use std::{collections::HashMap, sync::Mutex};
 
@@ -36,6 +41,7 @@ pub fn get_controller() -> Result<std::sync::MutexGuard<'static, Option<Controll
 }
 
 /// 启动服务器
+#[cfg(feature = "server")]
 pub fn start_server() -> Result<()> {
     let mut controller_guard = get_controller()?;
     if let Some(controller) = controller_guard.as_mut() {
@@ -46,6 +52,7 @@ pub fn start_server() -> Result<()> {
 }
 
 /// 停止服务器
+#[cfg(feature = "server")]
 pub fn stop_server() -> Result<()> {
     let mut controller_guard = get_controller()?;
     if let Some(controller) = controller_guard.as_mut() {
@@ -160,11 +167,15 @@ pub fn load_images_from_file(filename: &str) -> Result<()> {
     }
 }
 
-/// 保存图片数据到文件
-pub fn save_images_to_file(filename: &str, compression_level: u32) -> Result<()> {
+/// 保存图片数据到文件，文件头与状态文件相同
+pub fn save_images_to_file(
+    filename: &str,
+    header: &StateHeader,
+    compression_level: u32,
+) -> Result<()> {
     let controller_guard = get_controller()?;
     if let Some(controller) = controller_guard.as_ref() {
-        controller.save_images_to_file(filename, compression_level)
+        controller.save_images_to_file(filename, header, compression_level)
     } else {
         Err(anyhow::anyhow!("Controller not available"))
     }
@@ -1,4 +1,5 @@
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use crate::utils::fetch::record;
 use tracing::error;
 use tracing::info;
 use std::collections::HashMap;
@@ -314,6 +315,7 @@ impl HttpServer {
             file_path
         };
         info!("已保存 url:{}\tfilename:{}",url, image_data.filename);
+        record::record_image(&url, &image_data.u8_data);
         // println!("已保存 url:{}\tfilename:{}",url, image_data.filename);
         // 添加到图片HashMap，使用URL作为键
         self.images.write().unwrap().insert(url.clone(), image_data); // 使用write()而不是lock()
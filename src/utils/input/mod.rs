@@ -2,9 +2,10 @@ use crossterm::{
     event::{self, Event, KeyCode, KeyEvent},
     terminal::disable_raw_mode,
 };
+use once_cell::sync::Lazy;
 use tracing::warn;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
 use tokio::sync::{mpsc};
 
 // 用户命令枚举
@@ -14,65 +15,171 @@ pub enum UserCommand {
     Resume,     // 继续下载
     Status,     // 查看状态
     Quit,       // 退出程序
+    Interrupt,  // Ctrl+C，和退出相同，但不等待正在进行的下载
     Other(char), // 其他按键
 }
 
+/// 按键监听器，嵌套时按键和 Ctrl+C 发给最里层的，里层结束后恢复外层
+#[derive(Default)]
+struct Listeners {
+    senders: Vec<mpsc::Sender<UserCommand>>,
+    /// 读取按键的任务是否在运行，所有监听器共用一个，避免同时读取终端
+    polling: bool,
+}
+
+static LISTENERS: Lazy<Mutex<Listeners>> = Lazy::new(|| Mutex::new(Listeners::default()));
+/// 收到 Ctrl+C 的次数，所有监听器都移除时清零
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+/// Ctrl+C 中止了下载，本次运行不再开始新的卷
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static CTRLC_HANDLER: Once = Once::new();
+
+/// 设置 Ctrl+C 处理器：有监听器时第一次 Ctrl+C 按退出处理，保存状态后结束，再按一次强制退出
+fn install_ctrlc_handler() {
+    CTRLC_HANDLER.call_once(|| {
+        if let Err(e) = ctrlc::set_handler(|| {
+            let count = INTERRUPTS.fetch_add(1, Ordering::SeqCst) + 1;
+            if count == 1
+                && let Some(tx) = active_listener()
+                && tx.try_send(UserCommand::Interrupt).is_ok()
+            {
+                INTERRUPTED.store(true, Ordering::SeqCst);
+                println!("\n收到 Ctrl+C 信号，正在保存下载状态，再按一次强制退出...");
+                return;
+            }
+            println!("\n收到 Ctrl+C 信号，立即退出...");
+            std::process::exit(130);
+        }) {
+            warn!("无法设置 Ctrl+C 处理器: {}", e);
+        }
+    });
+}
+
+/// 本次运行中是否有 Ctrl+C 中止了下载
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// 最里层的监听器
+fn active_listener() -> Option<mpsc::Sender<UserCommand>> {
+    LISTENERS.lock().unwrap().senders.last().cloned()
+}
+
+/// 移除监听器，之后的按键和 Ctrl+C 交给外层的监听器
+fn remove_listener(cmd_tx: &mpsc::Sender<UserCommand>) {
+    let mut listeners = LISTENERS.lock().unwrap();
+    listeners.senders.retain(|tx| !tx.same_channel(cmd_tx));
+    if listeners.senders.is_empty() {
+        INTERRUPTS.store(0, Ordering::SeqCst);
+    }
+}
+
 // 修改按键监听器创建函数
 pub fn create_key_listener() -> (mpsc::Sender<()>, mpsc::Receiver<UserCommand>) {
     let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
     let (cmd_tx, cmd_rx) = mpsc::channel(10);
-    
-    let running = Arc::new(AtomicBool::new(true));
-    
-    // 设置 Ctrl+C 处理器 - 立即退出
-    if let Err(e) = ctrlc::set_handler(move || {
-        println!("\n收到 Ctrl+C 信号，立即退出...");
-        std::process::exit(0);
-    }) {
-        warn!("无法设置 Ctrl+C 处理器: {}", e);
+
+    install_ctrlc_handler();
+    let start_polling = {
+        let mut listeners = LISTENERS.lock().unwrap();
+        listeners.senders.push(cmd_tx.clone());
+        !std::mem::replace(&mut listeners.polling, true)
+    };
+    if start_polling {
+        tokio::spawn(poll_keys());
     }
-    
+
     tokio::spawn(async move {
-        // 启用原始模式
-        // if let Err(e) = enable_raw_mode() {
-        //     eprintln!("无法启用原始模式: {}", e);
-        //     return;
-        // }
-        
-        while running.load(Ordering::Relaxed) {
-            // 检查是否收到停止信号
-            if let Ok(()) = stop_rx.try_recv() {
-                running.store(false, Ordering::Relaxed);
-                break;
-            }
-            
-            // 使用非阻塞方式检查按键事件
-            if let Ok(true) = event::poll(std::time::Duration::from_millis(100)) {
-                if let Event::Key(KeyEvent { code, modifiers: _, .. }) = event::read().unwrap() {
-                    let command = match code {
-                        KeyCode::Char('q') | KeyCode::Char('Q') => UserCommand::Quit,
-                        KeyCode::Char('p') | KeyCode::Char('P') => UserCommand::Pause,
-                        KeyCode::Char('r') | KeyCode::Char('R') => UserCommand::Resume,
-                        KeyCode::Char('s') | KeyCode::Char('S') => UserCommand::Status,
-                        KeyCode::Char(c) => UserCommand::Other(c),
-                        _ => continue, // 忽略其他按键
-                    };
-                    
-                    // 发送命令
-                    if cmd_tx.send(command).await.is_err() {
-                        break; // 接收端已断开
-                    }
-                }
-            }
-            
-            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-        }
-        
-        // 禁用原始模式
-        if let Err(e) = disable_raw_mode() {
-            eprintln!("无法禁用原始模式: {}", e);
+        // 收到停止信号或者接收端断开后移除，之后的 Ctrl+C 由外层处理，没有外层时直接退出
+        tokio::select! {
+            _ = stop_rx.recv() => {}
+            _ = cmd_tx.closed() => {}
         }
+        remove_listener(&cmd_tx);
     });
-    
+
     (stop_tx, cmd_rx)
-}
\ No newline at end of file
+}
+
+/// 读取按键发给最里层的监听器，没有监听器时结束
+async fn poll_keys() {
+    loop {
+        let Some(cmd_tx) = ({
+            let mut listeners = LISTENERS.lock().unwrap();
+            let active = listeners.senders.last().cloned();
+            if active.is_none() {
+                listeners.polling = false;
+            }
+            active
+        }) else {
+            break;
+        };
+
+        // 使用非阻塞方式检查按键事件
+        if let Ok(true) = event::poll(std::time::Duration::from_millis(100))
+            && let Ok(Event::Key(KeyEvent { code, .. })) = event::read()
+        {
+            let command = match code {
+                KeyCode::Char('q') | KeyCode::Char('Q') => Some(UserCommand::Quit),
+                KeyCode::Char('p') | KeyCode::Char('P') => Some(UserCommand::Pause),
+                KeyCode::Char('r') | KeyCode::Char('R') => Some(UserCommand::Resume),
+                KeyCode::Char('s') | KeyCode::Char('S') => Some(UserCommand::Status),
+                KeyCode::Char(c) => Some(UserCommand::Other(c)),
+                _ => None, // 忽略其他按键
+            };
+            // 接收端已断开时监听器随后会被移除
+            if let Some(command) = command {
+                let _ = cmd_tx.send(command).await;
+            }
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    // 禁用原始模式
+    if let Err(e) = disable_raw_mode() {
+        eprintln!("无法禁用原始模式: {}", e);
+    }
+}
+
+/// 询问用户是否继续(y/n)，如果已经给出答案则直接返回
+pub fn ask_yes_no(prompt: &str, answer: Option<bool>) -> std::io::Result<bool> {
+    if let Some(answer) = answer {
+        return Ok(answer);
+    }
+    println!("{}", prompt);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_nested_listeners() {
+        let (outer_stop, mut outer_rx) = create_key_listener();
+        let (inner_stop, mut inner_rx) = create_key_listener();
+        let (inner_tx, outer_tx) = {
+            let listeners = LISTENERS.lock().unwrap();
+            let n = listeners.senders.len();
+            (
+                listeners.senders[n - 1].clone(),
+                listeners.senders[n - 2].clone(),
+            )
+        };
+        assert!(active_listener().unwrap().same_channel(&inner_tx));
+
+        inner_stop.send(()).await.unwrap();
+        drop(inner_tx);
+        // 监听器移除后接收端结束
+        assert!(inner_rx.recv().await.is_none());
+        assert!(active_listener().unwrap().same_channel(&outer_tx));
+
+        outer_stop.send(()).await.unwrap();
+        drop(outer_tx);
+        assert!(outer_rx.recv().await.is_none());
+        assert!(active_listener().is_none());
+    }
+}
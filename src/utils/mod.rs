@@ -1,11 +1,16 @@
 pub mod config;
 pub mod progressbar;
+#[cfg(feature = "browser")]
 pub mod check_single_file;
+#[cfg(feature = "browser")]
 pub mod download;
 pub mod terminal;
 pub mod httpclient;
+pub mod fetch;
+#[cfg(feature = "browser")]
 pub mod browser;
-// pub mod html;
+pub mod html;
 pub mod epub;
 pub mod httpserver;
-pub mod input;
\ No newline at end of file
+pub mod input;
+pub mod snapshot;
\ No newline at end of file
@@ -1,5 +1,5 @@
 pub mod progress_monitor;
-use std::sync::{Arc, atomic::AtomicUsize};
+use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize}};
 
 use indicatif::{ProgressBar, ProgressStyle};
 
@@ -70,6 +70,9 @@ impl ProgressMonitorBuilder {
             pb: Arc::new(pb),
             completed: Arc::new(AtomicUsize::new(0)),
             errors: Arc::new(AtomicUsize::new(0)),
+            blocks: Arc::new(AtomicUsize::new(0)),
+            concurrency: Arc::new(AtomicUsize::new(0)),
+            paused: Arc::new(AtomicBool::new(false)),
             total: self.total,
             prefix: self.prefix
         }
@@ -3,7 +3,7 @@
 use indicatif::{ProgressBar, ProgressStyle};
 use std::{sync::{
     Arc,
-    atomic::{AtomicUsize, Ordering},
+    atomic::{AtomicBool, AtomicUsize, Ordering},
 }, time::Duration};
 
 /// 进度监控器
@@ -12,6 +12,12 @@ pub struct ProgressMonitor {
     pub pb: Arc<ProgressBar>,
     pub completed: Arc<AtomicUsize>,
     pub errors: Arc<AtomicUsize>,
+    /// 遇到反爬页面的次数
+    pub blocks: Arc<AtomicUsize>,
+    /// 当前的并发数，为0时不显示
+    pub concurrency: Arc<AtomicUsize>,
+    /// 是否暂停
+    pub paused: Arc<AtomicBool>,
     pub total: usize,
     pub prefix: String,
 }
@@ -36,6 +42,9 @@ impl ProgressMonitor {
             pb: Arc::new(pb),
             completed: Arc::new(AtomicUsize::new(0)),
             errors: Arc::new(AtomicUsize::new(0)),
+            blocks: Arc::new(AtomicUsize::new(0)),
+            concurrency: Arc::new(AtomicUsize::new(0)),
+            paused: Arc::new(AtomicBool::new(false)),
             total,
             prefix: prefix.to_string(),
         }
@@ -83,27 +92,75 @@ impl ProgressMonitor {
         self.errors.load(Ordering::Relaxed)
     }
 
+    /// 记录一次反爬拦截
+    pub fn record_block(&self) {
+        self.blocks.fetch_add(1, Ordering::Relaxed);
+        self.update_message();
+    }
+
+    /// 获取拦截次数
+    pub fn block_count(&self) -> usize {
+        self.blocks.load(Ordering::Relaxed)
+    }
+
+    /// 设置显示的并发数
+    pub fn set_concurrency(&self, concurrency: usize) {
+        self.concurrency.store(concurrency, Ordering::Relaxed);
+        self.update_message();
+    }
+
+    /// 设置是否暂停
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        self.update_message();
+    }
+
+    /// 获取待下载数量
+    pub fn pending_count(&self) -> usize {
+        self.total.saturating_sub(self.completed_count())
+    }
+
+    /// 在进度条上方输出，不会打乱进度条
+    pub fn println(&self, message: &str) {
+        self.pb.suspend(|| println!("{}", message));
+    }
+
     /// 更新进度条消息
     fn update_message(&self) {
+        let mut message = if self.paused.load(Ordering::Relaxed) {
+            String::from("已暂停 ")
+        } else {
+            String::from("下载中…… ")
+        };
+        let concurrency = self.concurrency.load(Ordering::Relaxed);
+        if concurrency > 0 {
+            message.push_str(&format!("(并发 {}) ", concurrency));
+        }
+        let blocks = self.block_count();
+        if blocks > 0 {
+            message.push_str(&format!("({} 次拦截) ", blocks));
+        }
         let errors = self.error_count();
         if errors > 0 {
-            self.pb.set_message(format!("下载中…… ({} 个错误)", errors));
-        } else {
-            self.pb
-                .set_message("下载中…… ");
+            message.push_str(&format!("({} 个错误)", errors));
         }
+        self.pb.set_message(message);
     }
 
     /// 完成进度监控，显示错误统计，但不消失
     pub fn finish(&self) {
         let errors = self.error_count();
+        let blocks = self.block_count();
         
         // 设置最终消息，但不调用finish_with_message，这样进度条不会消失
+        let mut message = String::from("完成!");
+        if blocks > 0 {
+            message.push_str(&format!(" ({} 次拦截)", blocks));
+        }
         if errors > 0 {
-            self.pb.set_message(format!("完成! ({} 个错误)", errors));
-        } else {
-            self.pb.set_message("完成!");
+            message.push_str(&format!(" ({} 个错误)", errors));
         }
+        self.pb.set_message(message);
         
         // 确保进度条显示100%
         self.pb.set_position(self.total as u64);
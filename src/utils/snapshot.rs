@@ -0,0 +1,179 @@
+//! 状态和图片文件的安全写入
+//!
+//! 先写入临时文件并同步到磁盘，再替换原来的文件，原来的文件保留为 `.bak`。
+//! 写入中途崩溃时原来的文件不受影响，读取失败时自动使用上一次的文件。
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tracing::{error, warn};
+
+/// 上一次保存的文件的路径
+pub fn backup_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    with_suffix(path.as_ref(), ".bak")
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".tmp")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// 安全地写入文件，`write` 写入内容，原来的文件保留为 `.bak`
+pub fn write_atomic<P, F>(path: P, write: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    let path = path.as_ref();
+    let temp = temp_path(path);
+    let result: Result<()> = (|| {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        write(&mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e).with_context(|| format!("写入临时文件失败：{}", temp.display()));
+    }
+
+    if path.exists() {
+        std::fs::rename(path, backup_path(path))
+            .with_context(|| format!("备份文件失败：{}", path.display()))?;
+    }
+    std::fs::rename(&temp, path).with_context(|| format!("替换文件失败：{}", path.display()))?;
+    sync_parent(path);
+    Ok(())
+}
+
+/// 同步目录，保证重命名写入磁盘
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Err(e) = File::open(parent).and_then(|dir| dir.sync_all()) {
+            warn!("同步目录失败 {}：{}", parent.display(), e);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// 读取文件，文件不存在或损坏时读取上一次保存的 `.bak`
+pub fn load_with_backup<P, T, F>(path: P, load: F) -> Result<T>
+where
+    P: AsRef<Path>,
+    F: Fn(&Path) -> Result<T>,
+{
+    let path = path.as_ref();
+    let err = match load(path) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    let backup = backup_path(path);
+    if !backup.exists() {
+        return Err(err);
+    }
+    error!(
+        "读取失败，改用上一次保存的文件 {}：{:#}",
+        backup.display(),
+        err
+    );
+    eprintln!(
+        "读取失败，改用上一次保存的文件 {}：{:#}",
+        backup.display(),
+        err
+    );
+    load(&backup).map_err(|_| err)
+}
+
+/// 文件或上一次保存的文件是否存在
+pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    path.exists() || backup_path(path).exists()
+}
+
+/// 移动文件和它的备份
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    for (from, to) in [
+        (from.to_path_buf(), to.to_path_buf()),
+        (backup_path(from), backup_path(to)),
+    ] {
+        if from.exists() {
+            std::fs::rename(&from, &to).with_context(|| {
+                format!("移动文件失败：{} -> {}", from.display(), to.display())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// 删除文件和它的备份
+pub fn remove<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref();
+    for path in [path.to_path_buf(), backup_path(path), temp_path(path)] {
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap_or_else(|e| {
+                eprintln!("删除临时数据失败 file:{}  error:{}", path.display(), e)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &Path) -> Result<String> {
+        let content = std::fs::read_to_string(path)?;
+        anyhow::ensure!(content.starts_with("ok"), "文件损坏");
+        Ok(content)
+    }
+
+    #[test]
+    fn test_write_and_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vol.state.json");
+        assert!(!exists(&path));
+
+        write_atomic(&path, |w| Ok(w.write_all(b"ok 1")?)).unwrap();
+        write_atomic(&path, |w| Ok(w.write_all(b"ok 2")?)).unwrap();
+        assert_eq!(load_with_backup(&path, read).unwrap(), "ok 2");
+        assert_eq!(std::fs::read_to_string(backup_path(&path)).unwrap(), "ok 1");
+
+        // 写入失败时原来的文件不变
+        let result = write_atomic(&path, |_| Err(anyhow::anyhow!("中断")));
+        assert!(result.is_err());
+        assert_eq!(load_with_backup(&path, read).unwrap(), "ok 2");
+        assert!(!temp_path(&path).exists());
+
+        // 文件损坏或者丢失时使用备份
+        std::fs::write(&path, "{\"url\":").unwrap();
+        assert_eq!(load_with_backup(&path, read).unwrap(), "ok 1");
+        std::fs::remove_file(&path).unwrap();
+        assert!(exists(&path));
+        assert_eq!(load_with_backup(&path, read).unwrap(), "ok 1");
+
+        remove(&path);
+        assert!(!exists(&path));
+    }
+}
@@ -0,0 +1,83 @@
+//! 使用录制的页面离线运行完整的下载、检查和打包流程
+#![cfg(feature = "epub")]
+
+use std::path::Path;
+use std::sync::Arc;
+
+use novel_packer::core::state::{DOWNLOAD_DIR, list_states, load_state};
+use novel_packer::source::bilinovel::parser::get_bilinovel;
+use novel_packer::utils::fetch::Fetcher;
+use novel_packer::utils::fetch::replay::ReplayBackend;
+use novel_packer::{DownloadOptions, Pipeline, SiteDefinition, VolumeSelection};
+
+#[tokio::test]
+async fn test_pipeline_with_replay() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bilinovel");
+    let work = tempfile::tempdir().unwrap();
+    std::env::set_current_dir(work.path()).unwrap();
+    for dir in [
+        "config",
+        "temp/download",
+        "temp/images",
+        "temp/temp",
+        "output",
+    ] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    // 使用随机端口，避免和正在运行的程序冲突
+    std::fs::write(
+        "config/http.json",
+        r#"{"regex_pattern": [], "output_path": "./output/images", "wait_time": 0,
+            "send_to_rust": false, "server_port": 0, "save_to_file": false, "open_download": false}"#,
+    )
+    .unwrap();
+
+    let mut novel = get_bilinovel(
+        "https://www.linovelib.com/novel/1.html",
+        Arc::new(SiteDefinition::bilinovel()),
+    )
+    .unwrap();
+    novel.fetcher = Fetcher::new(ReplayBackend::open(&fixtures).unwrap());
+    let mut pipeline = Pipeline::new(novel);
+
+    pipeline.display().await.unwrap();
+    assert_eq!(pipeline.book_name(), "测试小说");
+    assert_eq!(pipeline.book().metadata.author, "测试作者");
+    assert_eq!(pipeline.book().volumes.len(), 1);
+
+    let options = DownloadOptions {
+        volumes: Some(VolumeSelection::Indices(vec![0..=0])),
+        resume: Some(false),
+        check: Some(true),
+        pack: Some(true),
+        clean: Some(false),
+        ..DownloadOptions::default()
+    };
+    let reports = pipeline.download(&options).await.unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].error, None);
+    assert!(reports[0].failed_chapters.is_empty());
+    assert!(reports[0].output.as_ref().unwrap().exists());
+
+    // 状态按来源、书籍号和卷号保存
+    let states = list_states(DOWNLOAD_DIR);
+    assert_eq!(states.len(), 1);
+    assert_eq!(states[0].name, "第一卷");
+    let state = load_state("./temp/download/bilinovel/1/10.state.json").unwrap();
+    assert_eq!(state.header.source, "bilinovel");
+    assert_eq!(state.header.book_id, "1");
+    let volume = state.volume;
+    assert!(volume.pending_chapter_indices.is_empty());
+    assert_eq!(volume.chapters.len(), 2);
+    // 序章有两页，广告被删除，图片使用data-src
+    assert_eq!(volume.chapters[0].pages.len(), 2);
+    assert!(!volume.chapters[0].pages[0].contains("广告"));
+    assert_eq!(
+        volume.chapters[0].images[0].url,
+        "https://img3.readpai.com/1/100/1.png"
+    );
+    assert_eq!(
+        volume.cover.unwrap().url,
+        "https://img3.readpai.com/1/10/cover.png"
+    );
+}